@@ -0,0 +1,65 @@
+//! Maps shared by the benchmarks, generated by the simulator so that they
+//! look like real games.
+
+use polygen_bot::{
+    bot::Bot,
+    sim::{leak_bot_data, Game, Options},
+    BotData,
+};
+
+fn bot_data(name: &str) -> &'static BotData {
+    leak_bot_data(&format!(
+        r#"
+        cookie = ""
+        room = "bench"
+        auto_ready = false
+        name = "{}"
+        "#,
+        name
+    ))
+}
+
+pub struct Fixture {
+    pub name: String,
+    pub game: Game,
+}
+
+impl Fixture {
+    /// The first bot, holding its fog-of-war view of the game.
+    #[inline]
+    pub fn bot(&self) -> &Bot {
+        self.game.bot(0)
+    }
+}
+
+/// Small and large, hexagon and square maps, played for a few turns
+/// (`early`) and for a few hundred (`late`).
+pub fn fixtures() -> Vec<Fixture> {
+    let mut fixtures = Vec::new();
+
+    for (size, side) in [("small", 20), ("large", 60)] {
+        for (mode, name) in [("六边形", "hexagon"), ("四边形", "square")] {
+            for (phase, turns) in [("early", 30), ("late", 300)] {
+                let options = Options {
+                    mode: mode.to_string(),
+                    width: side,
+                    height: side,
+                    ..Default::default()
+                };
+
+                let mut game = Game::new(&[bot_data("a"), bot_data("b")], &options, 0).unwrap();
+
+                for _ in 0..turns {
+                    game.step();
+                }
+
+                fixtures.push(Fixture {
+                    name: format!("{} {} {}", size, name, phase),
+                    game,
+                });
+            }
+        }
+    }
+
+    fixtures
+}
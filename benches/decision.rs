@@ -0,0 +1,59 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use fastrand::Rng;
+use polygen_bot::{
+    bot::Bot,
+    strategy::{Budget, Context, GreedyExpandStrategy},
+};
+
+mod common;
+
+/// Runs `f` on a fresh strategy with everything `bot` knows.
+fn with_context<T>(bot: &Bot, f: impl FnOnce(&mut GreedyExpandStrategy, &mut Context) -> T) -> T {
+    let mut rng = Rng::with_seed(0);
+    let mut ctx = Context {
+        gm: &bot.gm,
+        my_color: bot.my_color,
+        teammates: &bot.teammates,
+        turn: bot.turn(),
+        inference: &bot.inference,
+        config: bot.config(),
+        rng: &mut rng,
+        budget: Budget::Rounds,
+        checks: 0,
+        timed_out: None,
+    };
+
+    f(&mut GreedyExpandStrategy::default(), &mut ctx)
+}
+
+fn expand(c: &mut Criterion) {
+    let mut group = c.benchmark_group("expand");
+
+    for fixture in common::fixtures() {
+        let bot = fixture.bot();
+
+        group.bench_function(&fixture.name, |b| {
+            b.iter(|| with_context(bot, |strategy, ctx| strategy.expand(ctx)))
+        });
+    }
+
+    group.finish();
+}
+
+fn move_to_target(c: &mut Criterion) {
+    let mut group = c.benchmark_group("move_to_target");
+
+    for fixture in common::fixtures() {
+        let bot = fixture.bot();
+
+        // A fresh strategy has no plan, so this always runs the full BFS.
+        group.bench_function(&fixture.name, |b| {
+            b.iter(|| with_context(bot, |strategy, ctx| strategy.move_to_target(ctx, 0)))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, expand, move_to_target);
+criterion_main!(benches);
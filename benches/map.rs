@@ -0,0 +1,41 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+mod common;
+
+fn neighbours(c: &mut Criterion) {
+    let mut group = c.benchmark_group("neighbours");
+
+    for fixture in common::fixtures() {
+        let gm = &fixture.bot().gm;
+        let tiles: Vec<_> = gm.iter().into_iter().map(|(pos, _)| pos).collect();
+
+        group.bench_function(&fixture.name, |b| {
+            b.iter(|| {
+                tiles
+                    .iter()
+                    .map(|&pos| gm.neighbours(black_box(pos)).len())
+                    .sum::<usize>()
+            })
+        });
+    }
+
+    group.finish();
+}
+
+fn distances(c: &mut Criterion) {
+    let mut group = c.benchmark_group("distances");
+
+    for fixture in common::fixtures() {
+        let gm = &fixture.bot().gm;
+        let center = (gm.height / 2, gm.width / 2);
+
+        group.bench_function(&fixture.name, |b| {
+            b.iter(|| gm.distances([black_box(center)], u32::MAX))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, neighbours, distances);
+criterion_main!(benches);
@@ -0,0 +1,203 @@
+use anyhow::{ensure, Context, Result};
+use log::info;
+use polygen_bot::{
+    sim::{bot_data, Game, Options, Outcome},
+    BotConfig, BotData,
+};
+use serde::Deserialize;
+use std::{env, fs, thread};
+
+const INITIAL_RATING: f64 = 1500.0;
+const K_FACTOR: f64 = 32.0;
+
+const fn default_games() -> u32 {
+    10
+}
+
+#[derive(Deserialize)]
+struct ArenaConfig<'a> {
+    /// Games played by every pair of bots.
+    #[serde(default = "default_games")]
+    games: u32,
+
+    #[serde(default)]
+    seed: u64,
+
+    #[serde(default)]
+    options: Options,
+
+    #[serde(borrow)]
+    bots: Vec<BotConfig<'a>>,
+}
+
+#[derive(Default)]
+struct Stats {
+    wins: u32,
+    draws: u32,
+    losses: u32,
+    turns: u64,
+    rating: f64,
+}
+
+impl Stats {
+    fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+}
+
+fn expected(rating: f64, opponent: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf((opponent - rating) / 400.0))
+}
+
+fn play(
+    bots: &[&'static BotData],
+    options: &Options,
+    jobs: &[(usize, usize, u64)],
+) -> Result<Vec<Outcome>> {
+    let workers = thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk = jobs.len().div_ceil(workers).max(1);
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = jobs
+            .chunks(chunk)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|&(a, b, seed)| {
+                            let outcome = Game::new(&[bots[a], bots[b]], options, seed)?.run();
+                            info!(
+                                "{} vs {} (seed {}): {} in {} turns",
+                                bots[a].bot.name,
+                                bots[b].bot.name,
+                                seed,
+                                match outcome.winner {
+                                    Some(0) => bots[a].bot.name,
+                                    Some(_) => bots[b].bot.name,
+                                    None => "nobody",
+                                },
+                                outcome.turns
+                            );
+                            Ok(outcome)
+                        })
+                        .collect::<Result<Vec<_>>>()
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("arena worker panicked"))
+            .collect::<Result<Vec<_>>>()
+            .map(|outcomes| outcomes.into_iter().flatten().collect())
+    })
+}
+
+fn main() -> Result<()> {
+    env_logger::builder()
+        .filter_level(log::LevelFilter::Warn)
+        .parse_default_env()
+        .init();
+
+    let path = env::args()
+        .nth(1)
+        .unwrap_or_else(|| "arena.toml".to_string());
+    let content: &'static str = Box::leak(
+        fs::read_to_string(&path)
+            .with_context(|| format!("could not read {}", path))?
+            .into_boxed_str(),
+    );
+    let config: ArenaConfig<'static> = toml::from_str(content)?;
+
+    ensure!(config.bots.len() >= 2, "the arena needs at least two bots");
+
+    for (i, bot) in config.bots.iter().enumerate() {
+        ensure!(
+            config.bots[..i].iter().all(|other| other.name != bot.name),
+            "duplicate bot name `{}`",
+            bot.name
+        );
+    }
+
+    let bots: Vec<&'static BotData> = config.bots.iter().map(|&bot| bot_data(bot)).collect();
+
+    // Every pair plays `games` games, swapping colors every other game.
+    let mut jobs = Vec::new();
+
+    for a in 0..bots.len() {
+        for b in a + 1..bots.len() {
+            for game in 0..config.games {
+                let seed = config.seed.wrapping_add(jobs.len() as u64);
+
+                if game % 2 == 0 {
+                    jobs.push((a, b, seed));
+                } else {
+                    jobs.push((b, a, seed));
+                }
+            }
+        }
+    }
+
+    let outcomes = play(&bots, &config.options, &jobs)?;
+
+    let mut stats: Vec<Stats> = bots
+        .iter()
+        .map(|_| Stats {
+            rating: INITIAL_RATING,
+            ..Default::default()
+        })
+        .collect();
+
+    for (&(a, b, _), outcome) in jobs.iter().zip(&outcomes) {
+        let score = match outcome.winner {
+            Some(0) => {
+                stats[a].wins += 1;
+                stats[b].losses += 1;
+                1.0
+            }
+            Some(_) => {
+                stats[a].losses += 1;
+                stats[b].wins += 1;
+                0.0
+            }
+            None => {
+                stats[a].draws += 1;
+                stats[b].draws += 1;
+                0.5
+            }
+        };
+
+        stats[a].turns += outcome.turns as u64;
+        stats[b].turns += outcome.turns as u64;
+
+        let delta = K_FACTOR * (score - expected(stats[a].rating, stats[b].rating));
+        stats[a].rating += delta;
+        stats[b].rating -= delta;
+    }
+
+    let mut order: Vec<usize> = (0..bots.len()).collect();
+    order.sort_unstable_by(|&a, &b| stats[b].rating.total_cmp(&stats[a].rating));
+
+    println!(
+        "{:<20} {:>8} {:>6} {:>6} {:>6} {:>9} {:>10}",
+        "bot", "rating", "win", "draw", "loss", "win rate", "avg turns"
+    );
+
+    for index in order {
+        let stats = &stats[index];
+        let games = stats.games().max(1);
+
+        println!(
+            "{:<20} {:>8.1} {:>6} {:>6} {:>6} {:>8.1}% {:>10.1}",
+            bots[index].bot.name,
+            stats.rating,
+            stats.wins,
+            stats.draws,
+            stats.losses,
+            stats.wins as f64 * 100.0 / games as f64,
+            stats.turns as f64 / games as f64
+        );
+    }
+
+    Ok(())
+}
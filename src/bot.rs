@@ -1,335 +1,281 @@
 use crate::{
-    map::{Land, LandType, Map, Pos},
+    event::{GameStart, Patch, Rank, UpdateTeams},
+    infer::Inference,
+    map::{Map, Pos},
+    opening::{Book, Opening},
+    strategy::{self, Budget, Context, Strategy},
     BotData,
 };
+use anyhow::{anyhow, Result};
 use fastrand::Rng;
-use std::collections::{HashSet, VecDeque};
+use std::time::{Duration, Instant};
 
-type Movement = (Pos, Pos, bool);
+pub type Movement = (Pos, Pos, bool);
+
+/// How long a turn lasts at speed 1.
+pub const BASE_TURN_DURATION: Duration = Duration::from_millis(500);
+
+/// Where the current game is in time. The server sends one patch per turn,
+/// so the turn is the number of patches received since `gameStart`.
+#[derive(Clone, Copy, Debug)]
+pub struct Clock {
+    pub turn: u32,
+    /// The room speed from `RoomConfig`, 1 if not configured.
+    pub speed: f64,
+    turn_started_at: Instant,
+}
+
+impl Clock {
+    pub fn new(speed: f64) -> Self {
+        Self {
+            turn: 0,
+            speed,
+            turn_started_at: Instant::now(),
+        }
+    }
+
+    fn tick(&mut self) {
+        self.turn += 1;
+        self.turn_started_at = Instant::now();
+    }
+
+    #[inline]
+    pub fn turn_duration(&self) -> Duration {
+        BASE_TURN_DURATION.div_f64(self.speed)
+    }
+
+    /// Time left before the next patch is due.
+    #[inline]
+    pub fn remaining(&self) -> Duration {
+        self.turn_duration()
+            .saturating_sub(self.turn_started_at.elapsed())
+    }
+}
 
 pub struct Bot {
     pub gm: Map,
     pub my_color: u8,
-    pub target: Option<Pos>,
     pub teammates: Vec<u8>,
-    from: Option<Pos>,
+    pub inference: Inference,
     config: &'static BotData,
     rng: Rng,
+    strategy: Box<dyn Strategy>,
+    book: Book,
+    opening: Option<Opening>,
+    last_move: Option<Movement>,
+    /// When choosing `last_move` ran out of time, see `Context::timed_out`.
+    timed_out: Option<u32>,
+    clock: Clock,
+}
+
+/// Combines a seed and a game id into a new seed (splitmix64).
+const fn mix(seed: u64, game_id: u64) -> u64 {
+    let mut z = seed ^ game_id.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
 }
 
 impl Bot {
-    pub fn new(config: &'static BotData) -> Self {
-        Self {
+    pub fn new(config: &'static BotData) -> Result<Self> {
+        let strategy = strategy::from_name(config.bot.strategy)
+            .ok_or_else(|| anyhow!("unknown strategy `{}`", config.bot.strategy))?;
+        let book = Book::from_name(config.bot.opening)
+            .ok_or_else(|| anyhow!("unknown opening `{}`", config.bot.opening))?;
+
+        Ok(Self {
             config,
-            rng: Rng::new(),
+            rng: config.bot.seed.map_or_else(Rng::new, Rng::with_seed),
             my_color: 0,
-            target: None,
-            from: None,
             gm: Map::new(),
             teammates: Vec::new(),
-        }
+            inference: Inference::default(),
+            strategy,
+            book,
+            opening: None,
+            last_move: None,
+            timed_out: None,
+            clock: Clock::new(1.0),
+        })
     }
 
-    fn move_to(&self, from: Pos, to: Pos) -> Movement {
-        let from_land = &self.gm[from];
-        let to_land = &self.gm[to];
-
-        let mut half_tag = false;
-
-        if to_land.color != self.my_color
-            && to_land.color != 0
-            && (from_land.amount - 1) / 2 > to_land.amount
-            && !self.teammates.contains(&to_land.color)
-        {
-            for neighbour in self.gm.neighbours(from) {
-                let land = &self.gm[neighbour];
-
-                if land.color != self.my_color
-                    && land.color != 0
-                    && matches!(land.r#type, LandType::City | LandType::Land)
-                    && neighbour != to
-                    && !self.teammates.contains(&land.color)
-                {
-                    half_tag = true;
-                    break;
-                }
-            }
-        }
-
-        if to_land.r#type == LandType::City
-            && to_land.color == 0
-            && from_land.amount > 25
-            && !half_tag
-        {
-            for neighbour in self.gm.neighbours(from) {
-                let land = &self.gm[neighbour];
-
-                if land.color != self.my_color
-                    && !self.teammates.contains(&land.color)
-                    && matches!(land.r#type, LandType::City | LandType::Land)
-                    && neighbour != to
-                {
-                    half_tag = true;
-                    break;
-                }
-            }
-        }
-
-        (from, to, half_tag)
+    pub fn reset(&mut self) {
+        self.strategy.reset();
+        self.opening = None;
+        self.last_move = None;
     }
 
-    fn new_target(&mut self) -> Option<Pos> {
-        let mut targets = Vec::new();
+    #[inline]
+    pub const fn config(&self) -> &'static BotData {
+        self.config
+    }
 
-        for (pos, land) in self.gm.iter() {
-            if self.gm.accessible(pos)
-                && !matches!(land.r#type, LandType::Unknown | LandType::UnknownCity)
-                && land.color != self.my_color
-                && !self.teammates.contains(&land.color)
-            {
-                targets.push(pos);
-            }
-        }
+    #[inline]
+    pub const fn clock(&self) -> &Clock {
+        &self.clock
+    }
 
-        self.rng.shuffle(&mut targets);
-
-        let get_score = |&pos: &Pos| {
-            let land = &self.gm[pos];
-            match land.r#type {
-                LandType::General => 1,
-                LandType::City if land.color != 0 => 1,
-                LandType::City => 5,
-                LandType::Land if land.color != 0 => 2,
-                LandType::Land => 3,
-                _ => 9,
-            }
-        };
+    #[inline]
+    pub const fn turn(&self) -> u32 {
+        self.clock.turn
+    }
 
-        targets.sort_unstable_by_key(|target| get_score(target));
+    #[inline]
+    pub fn target(&self) -> Option<Pos> {
+        self.strategy.target()
+    }
 
-        targets.first().copied()
+    #[inline]
+    pub fn from(&self) -> Option<Pos> {
+        self.strategy.from()
     }
 
-    pub fn expand(&mut self) -> Option<Movement> {
-        let mut moves = Vec::new();
-
-        for (from, from_land) in self.gm.iter() {
-            if from_land.color == self.my_color {
-                for to in self.gm.neighbours(from) {
-                    let to_land = &self.gm[to];
-
-                    let delta = if to_land.r#type == LandType::City && to_land.color != 0 {
-                        2
-                    } else {
-                        1
-                    };
-
-                    if to_land.color != self.my_color
-                        && from_land.amount > to_land.amount + delta
-                        && !self.teammates.contains(&to_land.color)
-                    {
-                        moves.push((from, to));
-                    }
-                }
-            }
-        }
+    #[inline]
+    pub fn plan(&self) -> Vec<Pos> {
+        self.strategy.plan()
+    }
 
-        self.rng.shuffle(&mut moves);
-
-        const fn get_score(from_land: &Land, to_land: &Land) -> u8 {
-            let from_score = match from_land.r#type {
-                LandType::Land => 1,
-                LandType::City => 2,
-                LandType::General => 3,
-                _ => 4,
-            };
-
-            let to_score = match to_land.r#type {
-                LandType::General => 10,
-                LandType::City if to_land.color != 0 => 20,
-                LandType::City => 40,
-                LandType::Land if to_land.color != 0 => 30,
-                LandType::Land => 50,
-                _ => 90,
-            };
-
-            from_score + to_score
-        }
+    #[inline]
+    pub const fn last_move(&self) -> Option<Movement> {
+        self.last_move
+    }
 
-        moves.sort_unstable_by(|&(from_a, to_a), &(from_b, to_b)| {
-            let score_a = get_score(&self.gm[from_a], &self.gm[to_a]);
-            let score_b = get_score(&self.gm[from_b], &self.gm[to_b]);
+    /// Which check of the deadline found it passed while choosing the last
+    /// move, if any did; see [`Bot::next_move_replayed`].
+    #[inline]
+    pub const fn timed_out(&self) -> Option<u32> {
+        self.timed_out
+    }
 
-            if score_a != score_b {
-                score_a.cmp(&score_b)
-            } else {
-                (self.gm[from_b].amount - self.gm[to_b].amount)
-                    .cmp(&(self.gm[from_a].amount - self.gm[to_a].amount))
-            }
-        });
+    /// Switches to a reloaded config between games, keeping the strategy
+    /// and the opening book chosen by the old one.
+    #[inline]
+    pub fn set_config(&mut self, config: &'static BotData) {
+        self.config = config;
+    }
 
-        match moves.first() {
-            Some(&(from, to)) => {
-                if Some(from) == self.from && Some(to) != self.target {
-                    self.target = None;
-                }
+    /// Reseeds the RNG used by the strategy, making its choices reproducible.
+    pub fn seed(&mut self, seed: u64) {
+        self.rng.seed(seed);
+    }
 
-                Some(self.move_to(from, to))
-            }
-            None => self.move_to_target(0),
+    /// Reseeds the RNG for game `game_id` from the configured `seed`, if
+    /// there is one.
+    pub fn reseed(&mut self, game_id: u64) {
+        if let Some(seed) = self.config.bot.seed {
+            self.rng.seed(mix(seed, game_id));
         }
     }
 
-    fn move_to_target(&mut self, try_time: u8) -> Option<Movement> {
-        if try_time >= self.config.bot.calc_cnt {
-            return None;
-        }
+    pub fn start(&mut self, game_start: GameStart) {
+        self.reset();
+        self.gm = Map::from(game_start.maybe_map);
+        self.my_color = if game_start.my_color == -1 {
+            0
+        } else {
+            game_start.my_color as u8
+        };
+        self.clock = Clock::new(self.config.room.and_then(|room| room.speed).unwrap_or(1.0));
+        self.gm.remember(0);
+        self.inference.clear();
+        self.opening = self
+            .book
+            .plan(&self.gm, self.my_color, self.config.bot.opening_turns);
+    }
 
-        if self.target.is_none()
-            || matches!(&self.target, Some(target) if self.gm[*target].color == self.my_color
-                                                   || self.teammates.contains(&self.gm[*target].color))
-        {
-            self.target = self.new_target();
-            self.from = None;
+    pub fn patch(&mut self, patch: &Patch) {
+        for &(id, data) in &patch.updates {
+            self.gm[id].patch(data);
         }
 
-        let target = self.target?;
-
-        let get_score = |pos: Pos| {
-            let land = &self.gm[pos];
-
-            if land.color == self.my_color || self.teammates.contains(&land.color) {
-                land.amount - 1
-            } else {
-                -land.amount - 1
-            }
-        };
-
-        let mut max_ans = None;
-        let mut max_score = f64::MIN;
-        let mut new_from = None;
+        self.clock.tick();
+        self.gm.remember(self.clock.turn);
+        self.inference.update(
+            &self.gm,
+            self.my_color,
+            &self.teammates,
+            &patch.rank,
+            self.clock.turn,
+        );
+    }
 
-        let mut q = VecDeque::new();
-        let mut vis = HashSet::new();
+    /// Recomputes which colors in `rank` belong to players on our team.
+    pub fn update_teammates(&mut self, rank: &Rank, teams: &UpdateTeams) {
+        let bot_name = self.config.bot.name;
 
-        let mut found_enemy = false;
+        self.teammates.clear();
 
-        for (_, land) in self.gm.iter() {
-            if land.color != self.my_color
-                && land.color != 0
-                && !self.teammates.contains(&land.color)
-                && matches!(
-                    land.r#type,
-                    LandType::General | LandType::City | LandType::Land
-                )
+        for (_, color, username, _, _) in rank {
+            if *color != -1
+                && (*color as u8) != self.my_color
+                && username != bot_name
+                && teams.iter().any(|(_, players)| {
+                    players.iter().any(|player| player == bot_name) && players.contains(username)
+                })
             {
-                found_enemy = true;
-                break;
+                self.teammates.push(*color as u8);
             }
         }
+    }
 
-        let mut bfs = |from: Pos| {
-            let mut tmp_ans = None;
-            let mut tmp_score = f64::MIN;
-            let mut tmp_from = None;
-
-            for try_time in 0..self.config.bot.calc_cnt {
-                q.clear();
-                vis.clear();
-
-                q.push_back((from, get_score(from), 0, None));
-                vis.insert(from);
-
-                while let Some((cur, amount, length, ans)) = q.pop_front() {
-                    if cur == target {
-                        let score =
-                            amount as f64 / (length as f64).powf(self.config.bot.score_power);
-
-                        if score > tmp_score && !(amount < 0 && length < 2) {
-                            tmp_score = score;
-                            tmp_ans = ans;
-
-                            tmp_from = Some(from);
-
-                            continue;
-                        }
-                    }
-
-                    if !found_enemy && length > 6 {
-                        continue;
-                    }
-
-                    let mut neighbours = self.gm.neighbours(cur);
-                    self.rng.shuffle(&mut neighbours);
-
-                    for nxt in neighbours {
-                        if self.gm[nxt].r#type == LandType::General
-                            && self.teammates.contains(&self.gm[nxt].color)
-                            || !vis.insert(nxt)
-                        {
-                            continue;
-                        }
-
-                        if cur == from {
-                            q.push_back((nxt, amount + get_score(nxt), length + 1, Some(nxt)));
-                        } else {
-                            q.push_back((nxt, amount + get_score(nxt), length + 1, ans));
-                        }
-                    }
-                }
-
-                if try_time == 2 && tmp_score < max_score / 2.0 {
-                    break;
-                }
-            }
+    /// How long a live bot may take to choose a move, see
+    /// `BotConfig::think_ms`.
+    pub fn think_time(&self) -> Duration {
+        self.config
+            .bot
+            .think_ms
+            .map_or_else(|| self.clock.remaining() / 2, Duration::from_millis)
+    }
 
-            if tmp_score > max_score {
-                max_score = tmp_score;
-                max_ans = tmp_ans;
-                new_from = tmp_from;
-            }
-        };
+    /// Chooses a move after a fixed amount of search, the same every time
+    /// for the same seed.
+    #[inline]
+    pub fn next_move(&mut self) -> Option<Movement> {
+        self.decide(Budget::Rounds)
+    }
 
-        match &self.from {
-            Some(from) => bfs(*from),
-            _ => {
-                'outer: for (pos, land) in self.gm.iter() {
-                    if land.color == self.my_color && land.amount > 1 {
-                        for neighbour in self.gm.neighbours(pos) {
-                            let land = &self.gm[neighbour];
-
-                            if land.color != self.my_color
-                                && land.color != 0
-                                && !self.teammates.contains(&land.color)
-                                && matches!(land.r#type, LandType::Land | LandType::City)
-                            {
-                                continue 'outer;
-                            }
-                        }
-
-                        bfs(pos);
-                    }
-                }
-            }
-        }
+    /// Chooses a move, searching until `deadline` and then playing the best
+    /// move found by then.
+    #[inline]
+    pub fn next_move_by(&mut self, deadline: Instant) -> Option<Movement> {
+        self.decide(Budget::Until(deadline))
+    }
 
-        if max_ans.is_none() {
-            self.target = None;
-            return self.move_to_target(try_time + 1);
-        }
+    /// Chooses a move the way [`Bot::next_move_by`] did when it ran out of
+    /// time at `timed_out`, as reported by [`Bot::timed_out`].
+    #[inline]
+    pub fn next_move_replayed(&mut self, timed_out: Option<u32>) -> Option<Movement> {
+        self.decide(Budget::Replay(timed_out))
+    }
 
-        let max_ans = max_ans.unwrap();
+    fn decide(&mut self, budget: Budget) -> Option<Movement> {
+        self.timed_out = None;
 
-        if max_ans == target {
-            self.target = None;
+        if let Some(opening) = &mut self.opening {
+            match opening.next_move(&self.gm, self.my_color, self.clock.turn) {
+                Some(movement) => {
+                    self.last_move = movement;
+                    return movement;
+                }
+                None => self.opening = None,
+            }
         }
 
-        if self.from.is_none() {
-            self.from = new_from;
-        }
+        let mut ctx = Context {
+            gm: &self.gm,
+            my_color: self.my_color,
+            teammates: &self.teammates,
+            turn: self.clock.turn,
+            inference: &self.inference,
+            config: self.config,
+            rng: &mut self.rng,
+            budget,
+            checks: 0,
+            timed_out: None,
+        };
 
-        let ans = self.move_to(self.from.unwrap(), max_ans);
-        self.from = Some(max_ans);
-        Some(ans)
+        self.last_move = self.strategy.next_move(&mut ctx);
+        self.timed_out = ctx.timed_out;
+        self.last_move
     }
 }
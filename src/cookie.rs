@@ -0,0 +1,174 @@
+//! Where a bot's session cookie comes from, so that `config.toml` does not
+//! have to hold it:
+//!
+//! ```toml
+//! cookie = "__session=xxxxxx"                             # as is
+//! cookie = "__session=${POLYGEN_SESSION}"                 # from the environment
+//! cookie = { file = "cookie.txt" }                        # the whole file
+//! cookie = { file = "credentials.toml", key = "Bot" }     # a key in a TOML table
+//! cookie = { command = "pass show polygen" }              # what a command prints
+//! cookie = { username = "Bot", password = "${PASSWORD}" }  # logging in, see `login`
+//! ```
+//!
+//! Files must not be readable by anyone but their owner. Cookies are
+//! resolved every time the bot connects, reconnections included, and never
+//! appear in `Debug` output.
+
+use crate::login::{self, Credentials};
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use serde::Deserialize;
+use std::{collections::HashMap, env, fmt, fs, path::Path, process};
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(untagged)]
+pub enum Cookie<'a> {
+    /// The cookie, with every `${VAR}` replaced by the environment variable
+    /// `VAR`.
+    Value(&'a str),
+    /// The trimmed contents of `file`, or with `key` its string value of
+    /// that name, the file being a TOML table.
+    File {
+        file: &'a str,
+        #[serde(default)]
+        key: Option<&'a str>,
+    },
+    /// The trimmed standard output of `command`, run by the shell.
+    Command { command: &'a str },
+    /// A session obtained by logging in to `base_url`.
+    #[serde(borrow)]
+    Login(Credentials<'a>),
+}
+
+impl fmt::Debug for Cookie<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Value(_) => f.write_str("Value(<redacted>)"),
+            Self::File { file, key } => f
+                .debug_struct("File")
+                .field("file", file)
+                .field("key", key)
+                .finish(),
+            Self::Command { command } => {
+                f.debug_struct("Command").field("command", command).finish()
+            }
+            Self::Login(credentials) => f.debug_tuple("Login").field(credentials).finish(),
+        }
+    }
+}
+
+/// Replaces every `${VAR}` in `template` by the environment variable `VAR`.
+pub(crate) fn interpolate(template: &str) -> Result<String> {
+    let mut res = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find("${") {
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| anyhow!("unterminated `${{` in cookie"))?;
+        let name = &rest[start + 2..start + end];
+
+        res.push_str(&rest[..start]);
+        res.push_str(&env::var(name).with_context(|| format!("environment variable `{}`", name))?);
+        rest = &rest[start + end + 1..];
+    }
+
+    res.push_str(rest);
+
+    Ok(res)
+}
+
+/// Fails if anyone but the owner of `path` may read it.
+#[cfg(unix)]
+pub(crate) fn check_permissions(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = fs::metadata(path)
+        .with_context(|| format!("failed to read {}", path.display()))?
+        .permissions()
+        .mode();
+
+    ensure!(
+        mode & 0o077 == 0,
+        "{} is accessible by other users (mode {:o}), run `chmod 600` on it",
+        path.display(),
+        mode & 0o777
+    );
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub(crate) fn check_permissions(_: &Path) -> Result<()> {
+    Ok(())
+}
+
+fn read(file: &str, key: Option<&str>) -> Result<String> {
+    let path = Path::new(file);
+
+    check_permissions(path)?;
+
+    let text =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+
+    match key {
+        None => Ok(text.trim().to_string()),
+        Some(key) => {
+            let table: HashMap<String, String> = toml::from_str(&text)
+                .with_context(|| format!("failed to parse {}", path.display()))?;
+
+            table
+                .get(key)
+                .map(|cookie| cookie.trim().to_string())
+                .ok_or_else(|| anyhow!("no key `{}` in {}", key, path.display()))
+        }
+    }
+}
+
+fn run(command: &str) -> Result<String> {
+    let (shell, flag) = if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+
+    let output = process::Command::new(shell)
+        .args([flag, command])
+        .output()
+        .with_context(|| format!("failed to run `{}`", command))?;
+
+    if !output.status.success() {
+        bail!(
+            "`{}` failed with {}: {}",
+            command,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(String::from_utf8(output.stdout)?.trim().to_string())
+}
+
+impl Cookie<'_> {
+    /// The cookie to connect to `base_url` with.
+    pub fn resolve(&self, base_url: &str) -> Result<String> {
+        let cookie = match self {
+            Self::Value(template) => interpolate(template)?,
+            Self::File { file, key } => read(file, *key)?,
+            Self::Command { command } => run(command)?,
+            Self::Login(credentials) => login::session(base_url, credentials)?,
+        };
+
+        ensure!(!cookie.is_empty(), "the cookie is empty");
+
+        Ok(cookie)
+    }
+
+    /// A new cookie after `base_url` rejected the last one, or `None` if
+    /// there is no way to get one.
+    pub fn refresh(&self, base_url: &str) -> Option<Result<String>> {
+        match self {
+            Self::Login(credentials) => Some(login::refresh(base_url, credentials)),
+            _ => None,
+        }
+    }
+}
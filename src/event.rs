@@ -1,7 +1,7 @@
 use crate::map::{MaybeLand, MaybeMap};
 use anyhow::Result;
 use rust_socketio::{Payload, RawClient};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 pub fn callback<T, R>(mut input: T) -> impl FnMut(Payload, RawClient) + 'static + Sync + Send
 where
@@ -17,7 +17,7 @@ where
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize, Clone)]
 pub struct GameStart {
     #[serde(rename = "maybeMap")]
     pub maybe_map: MaybeMap,
@@ -27,8 +27,9 @@ pub struct GameStart {
 }
 
 pub type Updates = Vec<(usize, MaybeLand)>;
+/// Per player: team, color (-1 for spectators), username, army and land.
 pub type Rank = Vec<(Option<f64>, i32, String, u32, u32)>;
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize, Clone)]
 pub struct Patch {
     pub updates: Updates,
     pub rank: Rank,
@@ -0,0 +1,305 @@
+//! Guesses where enemy generals are hiding.
+//!
+//! For every enemy color in `rank` an [`Inference`] keeps a probability for
+//! each tile of being that color's general, recomputed after every patch
+//! from what the bot has seen so far:
+//!
+//! - a general that is visible, or was last seen before the fog covered it,
+//!   takes all of the probability;
+//! - a tile can only hold a general if it was never seen as anything else,
+//!   since generals never move;
+//! - an army moves one step a turn and every army starts at the general, so
+//!   a tile first seen owned by the color on some turn is at most that many
+//!   steps from its general, see [`MAX_CLAIMS`];
+//! - territory grows outwards from the general, which is thus some way
+//!   behind the tiles the color was seen on: tiles about half the radius
+//!   of its land, as reported in `rank`, from them are favoured, the more so
+//!   the further they are from our general, as the side we see faces us.
+//!   Among equally likely tiles the one closest to the earliest claims is
+//!   guessed;
+//! - until a color has been seen at all, generals are assumed to be spread
+//!   out, so tiles far from our own general are favoured.
+//!
+//! The army counts in `rank` say nothing about where a general is and are
+//! not used.
+
+use crate::{
+    event::Rank,
+    map::{LandType, Map, Pos},
+};
+
+/// How many of the earliest tiles seen owned by a color bound where its
+/// general can be. Later ones rarely rule out anything the early ones do
+/// not, cost a BFS per patch each, and may be tiles handed over by an
+/// eliminated player rather than captured.
+pub const MAX_CLAIMS: usize = 8;
+
+/// Where one enemy's general might be.
+pub struct GeneralBelief {
+    pub color: u8,
+    /// Whether the color has been seen at all, and thus whether the belief
+    /// says more than "somewhere far away".
+    pub informed: bool,
+    /// Indexed by tile id, see [`Map::id`].
+    probs: Vec<f64>,
+    /// Summed distances to the earliest claims by tile id, breaking ties
+    /// between equally likely tiles; empty without claims.
+    spread: Vec<u32>,
+    width: usize,
+}
+
+impl GeneralBelief {
+    /// The probability that the general is at `pos`.
+    #[inline]
+    pub fn probability(&self, (i, j): Pos) -> f64 {
+        if i == 0 || j == 0 || j > self.width {
+            return 0.0;
+        }
+
+        self.probs
+            .get((i - 1) * self.width + j)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// The most likely position of the general, with its probability.
+    pub fn most_likely(&self) -> Option<(Pos, f64)> {
+        let spread = |id: usize| self.spread.get(id).copied().unwrap_or_default();
+        let mut best: Option<(usize, f64)> = None;
+
+        for (id, &prob) in self.probs.iter().enumerate().skip(1) {
+            if prob > 0.0
+                && best.is_none_or(|(best, max)| {
+                    prob > max || prob == max && spread(id) < spread(best)
+                })
+            {
+                best = Some((id, prob));
+            }
+        }
+
+        best.map(|(id, prob)| {
+            let pos = ((id - 1) / self.width + 1, (id - 1) % self.width + 1);
+            (pos, prob)
+        })
+    }
+}
+
+/// A tile first seen owned by `color` on `turn`.
+#[derive(Clone, Copy)]
+struct Claim {
+    color: u8,
+    pos: Pos,
+    turn: u32,
+}
+
+#[derive(Default)]
+pub struct Inference {
+    beliefs: Vec<GeneralBelief>,
+    /// Every change of a visible tile to an enemy color, in order.
+    claims: Vec<Claim>,
+    /// The owner of every tile when it was last visible, indexed by tile id.
+    owners: Vec<u8>,
+}
+
+impl Inference {
+    pub fn clear(&mut self) {
+        self.beliefs.clear();
+        self.claims.clear();
+        self.owners.clear();
+    }
+
+    #[inline]
+    pub fn beliefs(&self) -> &[GeneralBelief] {
+        &self.beliefs
+    }
+
+    #[inline]
+    pub fn belief(&self, color: u8) -> Option<&GeneralBelief> {
+        self.beliefs.iter().find(|belief| belief.color == color)
+    }
+
+    /// The most likely position of any enemy general among the colors that
+    /// have been seen, with its probability.
+    pub fn most_likely(&self) -> Option<(u8, Pos, f64)> {
+        self.beliefs
+            .iter()
+            .filter(|belief| belief.informed)
+            .filter_map(|belief| {
+                belief
+                    .most_likely()
+                    .map(|(pos, prob)| (belief.color, pos, prob))
+            })
+            .max_by(|a, b| a.2.total_cmp(&b.2))
+    }
+
+    /// Records the ownership changes in the latest patch, received on
+    /// `turn`, and recomputes every belief from them, the map, including
+    /// what is remembered behind the fog, and the latest `rank`.
+    pub fn update(&mut self, gm: &Map, my_color: u8, teammates: &[u8], rank: &Rank, turn: u32) {
+        self.beliefs.clear();
+
+        if self.owners.len() != gm.width * gm.height + 1 {
+            self.owners = gm.grid(0);
+        }
+
+        for (pos, land) in gm.iter() {
+            let id = gm.id(pos);
+
+            if gm.visible(pos) && self.owners[id] != land.color {
+                self.owners[id] = land.color;
+
+                if land.color != 0 && land.color != my_color {
+                    self.claims.push(Claim {
+                        color: land.color,
+                        pos,
+                        turn,
+                    });
+                }
+            }
+        }
+
+        let home: Vec<Pos> = gm
+            .iter()
+            .into_iter()
+            .filter(|(_, land)| land.r#type == LandType::General && land.color == my_color)
+            .map(|(pos, _)| pos)
+            .collect();
+        let from_home = gm.distances(home, u32::MAX);
+
+        for (_, color, _, _, land_count) in rank {
+            if *color < 0 {
+                continue;
+            }
+
+            let color = *color as u8;
+
+            if color == my_color || teammates.contains(&color) {
+                continue;
+            }
+
+            let belief = self.infer(gm, color, *land_count, &from_home);
+            self.beliefs.push(belief);
+        }
+    }
+
+    /// The summed distances from every tile to the early claims of
+    /// `color`, for tiles close enough to all of them to hold its general.
+    /// `None` if there are no claims or they contradict each other, e.g.
+    /// because a patch was missed.
+    fn reachable(&self, gm: &Map, color: u8) -> Option<Vec<Option<u32>>> {
+        let mut res: Option<Vec<Option<u32>>> = None;
+
+        for claim in self
+            .claims
+            .iter()
+            .filter(|claim| claim.color == color)
+            .take(MAX_CLAIMS)
+        {
+            let dist = gm.distances([claim.pos], claim.turn);
+
+            match res.as_mut() {
+                None => res = Some(dist),
+                Some(res) => res
+                    .iter_mut()
+                    .zip(dist)
+                    .for_each(|(sum, d)| *sum = sum.zip(d).map(|(sum, d)| sum + d)),
+            }
+        }
+
+        res.filter(|res| res.iter().any(Option::is_some))
+    }
+
+    fn infer(
+        &self,
+        gm: &Map,
+        color: u8,
+        land_count: u32,
+        from_home: &[Option<u32>],
+    ) -> GeneralBelief {
+        let mut probs = gm.grid(0.0);
+        let mut seen = Vec::new();
+
+        for (pos, _) in gm.iter() {
+            let land = gm.recall(pos);
+
+            if land.r#type == LandType::General && land.color == color {
+                probs[gm.id(pos)] = 1.0;
+
+                return GeneralBelief {
+                    color,
+                    informed: true,
+                    probs,
+                    spread: Vec::new(),
+                    width: gm.width,
+                };
+            }
+
+            if let Some(sighting) = gm.last_seen(pos) {
+                if sighting.land.color == color {
+                    seen.push(pos);
+                }
+            }
+        }
+
+        let informed = !seen.is_empty();
+        let from_seen = gm.distances(seen, u32::MAX);
+        let reachable = self.reachable(gm, color);
+        let radius = f64::from(land_count).sqrt().max(1.0);
+
+        let mut total = 0.0;
+
+        for (pos, land) in gm.iter() {
+            // Generals never move, so any tile seen as something else is out.
+            if !matches!(land.r#type, LandType::Unknown) || gm.last_seen(pos).is_some() {
+                continue;
+            }
+
+            if reachable
+                .as_ref()
+                .is_some_and(|reachable| reachable[gm.id(pos)].is_none())
+            {
+                continue;
+            }
+
+            let weight = if informed {
+                match from_seen[gm.id(pos)] {
+                    Some(d) => {
+                        let away = from_home[gm.id(pos)].map_or(0.0, f64::from) / 3.0;
+                        let off = (f64::from(d) - radius / 2.0).abs();
+                        ((away - off) / radius).exp()
+                    }
+                    None => 0.0,
+                }
+            } else {
+                match from_home[gm.id(pos)] {
+                    Some(d) => 1.0 + f64::from(d),
+                    None => 1.0,
+                }
+            };
+
+            probs[gm.id(pos)] = weight;
+            total += weight;
+        }
+
+        if total > 0.0 {
+            probs.iter_mut().for_each(|prob| *prob /= total);
+        }
+
+        let spread = reachable
+            .map(|reachable| {
+                reachable
+                    .into_iter()
+                    .map(|d| d.unwrap_or(u32::MAX))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        GeneralBelief {
+            color,
+            informed,
+            probs,
+            spread,
+            width: gm.width,
+        }
+    }
+}
@@ -0,0 +1,89 @@
+//! How long the bot takes to answer a patch.
+//!
+//! Latencies go into power-of-two buckets of microseconds, so percentiles
+//! are only accurate to within a factor of two, which is plenty to tell a
+//! comfortable game from one where the bot misses turns.
+
+use std::{fmt, time::Duration};
+
+const BUCKETS: usize = 24;
+
+#[derive(Clone, Default)]
+pub struct Latency {
+    /// `buckets[k]` counts latencies below `2^k` microseconds and not in an
+    /// earlier bucket; the last one also takes everything longer.
+    buckets: [u32; BUCKETS],
+    count: u32,
+    max: Duration,
+    /// Latencies longer than the turn they were measured in.
+    late: u32,
+}
+
+impl Latency {
+    /// Records one decision that took `elapsed` out of a turn of `budget`.
+    pub fn record(&mut self, elapsed: Duration, budget: Duration) {
+        let micros = elapsed.as_micros() as u64;
+        let bucket = (u64::BITS - micros.leading_zeros()) as usize;
+
+        self.buckets[bucket.min(BUCKETS - 1)] += 1;
+        self.count += 1;
+        self.max = self.max.max(elapsed);
+
+        if elapsed > budget {
+            self.late += 1;
+        }
+    }
+
+    #[inline]
+    pub const fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// An upper bound on the `q`-th quantile, `q` in `0.0..=1.0`.
+    pub fn quantile(&self, q: f64) -> Duration {
+        let rank = (q * self.count as f64).ceil().max(1.0) as u32;
+        let mut seen = 0;
+
+        for (bucket, &count) in self.buckets.iter().enumerate() {
+            seen += count;
+
+            if seen >= rank {
+                return Duration::from_micros(1 << bucket).min(self.max);
+            }
+        }
+
+        self.max
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+impl fmt::Display for Latency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} decisions, p50 < {:?}, p90 < {:?}, p99 < {:?}, max {:?}, {} over the turn",
+            self.count,
+            self.quantile(0.5),
+            self.quantile(0.9),
+            self.quantile(0.99),
+            self.max,
+            self.late
+        )?;
+
+        for (bucket, &count) in self.buckets.iter().enumerate() {
+            if count > 0 {
+                write!(
+                    f,
+                    "\n  < {:>10?}: {}",
+                    Duration::from_micros(1 << bucket),
+                    count
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -1,21 +1,58 @@
+use cookie::Cookie;
 use serde::Deserialize;
 use std::collections::HashMap;
 
-mod bot;
-mod event;
-mod map;
+pub mod bot;
+pub mod cookie;
+pub mod event;
+pub mod infer;
+pub mod latency;
+pub mod login;
+pub mod map;
+#[cfg(feature = "mock")]
+pub mod mock;
+pub mod opening;
+pub mod render;
+pub mod replay;
+pub mod sim;
 pub mod socket;
+pub mod strategy;
+pub mod validate;
+pub mod worker;
 
 #[macro_use]
 extern crate log;
 
-#[derive(Deserialize, Clone, Copy, Debug)]
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
 #[serde(untagged)]
 pub enum AutoReady {
     Unconditional(bool),
     Conditional { more_than: usize },
 }
 
+/// How a bot reconnects after losing its connection. Delays start at
+/// `min_delay_ms` and grow by half after every failed attempt, up to
+/// `max_delay_ms`, each with up to 50% random jitter. After `attempts` failed
+/// attempts the bot is reported as failed; `attempts = 0` never reconnects.
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(default)]
+pub struct ReconnectConfig {
+    pub attempts: u8,
+    pub min_delay_ms: u64,
+    pub max_delay_ms: u64,
+}
+
+impl Default for ReconnectConfig {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            attempts: 10,
+            min_delay_ms: 1000,
+            max_delay_ms: 30000,
+        }
+    }
+}
+
 const fn default_calc_cnt() -> u8 {
     1
 }
@@ -24,9 +61,27 @@ const fn default_score_power() -> f64 {
     1.0
 }
 
-#[derive(Deserialize, Clone, Copy, Debug)]
+const fn default_threat_radius() -> u32 {
+    4
+}
+
+const fn default_opening<'a>() -> &'a str {
+    "none"
+}
+
+const fn default_opening_turns() -> u32 {
+    25
+}
+
+const fn default_strategy<'a>() -> &'a str {
+    "greedy_expand"
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
 pub struct BotConfig<'a> {
-    pub cookie: &'a str,
+    /// See [`cookie`].
+    #[serde(borrow)]
+    pub cookie: Cookie<'a>,
     pub room: &'a str,
     pub auto_ready: AutoReady,
 
@@ -38,11 +93,52 @@ pub struct BotConfig<'a> {
     #[serde(default = "default_score_power")]
     pub score_power: f64,
 
+    /// Milliseconds a live bot may spend choosing each move, counted from
+    /// when the patch arrives; half of what is left of the turn at the room
+    /// speed if not set.
+    /// Searching stops there with the best move found so far. Simulated
+    /// games search `calc_cnt` times instead so they stay reproducible, and
+    /// replays stop where the recorded moves did.
+    #[serde(default)]
+    pub think_ms: Option<u64>,
+
     #[serde(default)]
     pub flag: bool,
+
+    #[serde(default = "default_strategy")]
+    pub strategy: &'a str,
+
+    /// See `opening::Book::from_name`.
+    #[serde(default = "default_opening")]
+    pub opening: &'a str,
+
+    /// The last turn played by the opening, at most `opening::MAX_TURNS`.
+    #[serde(default = "default_opening_turns")]
+    pub opening_turns: u32,
+
+    /// How many steps from our general an enemy army counts as a threat;
+    /// 0 never defends.
+    #[serde(default = "default_threat_radius")]
+    pub threat_radius: u32,
+
+    /// Gather armies into one stack against an enemy general we have seen
+    /// every this many turns; 0 never does.
+    #[serde(default)]
+    pub gather_interval: u32,
+
+    #[serde(default)]
+    pub render: bool,
+
+    /// Seeds the bot's RNG, mixed with the id of every game, so that the
+    /// same game is always played the same way. Random if not set.
+    #[serde(default)]
+    pub seed: Option<u64>,
+
+    #[serde(default)]
+    pub reconnect: ReconnectConfig,
 }
 
-#[derive(Deserialize, Clone, Copy, Debug)]
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
 pub struct RoomConfig<'a> {
     pub mode: Option<&'a str>,
     pub map: Option<&'a str>,
@@ -56,11 +152,14 @@ pub struct Config<'a> {
     #[serde(borrow)]
     pub bots: Vec<BotConfig<'a>>,
     pub rooms: HashMap<&'a str, RoomConfig<'a>>,
+    #[serde(default)]
+    pub replay_dir: Option<&'a str>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, PartialEq, Debug)]
 pub struct BotData {
     pub bot: BotConfig<'static>,
     pub room: Option<RoomConfig<'static>>,
     pub base_url: &'static str,
+    pub replay_dir: Option<&'static str>,
 }
@@ -0,0 +1,144 @@
+//! Logging in with a username and password instead of pasting a cookie:
+//!
+//! ```toml
+//! cookie = { username = "Bot", password = "${POLYGEN_PASSWORD}", store = "session.txt" }
+//! ```
+//!
+//! The bot posts the credentials as JSON to `path` on `base_url` and keeps
+//! the `__session` cookie the response sets. With `store` the cookie is also
+//! saved to that file, readable only by its owner, and reused until the
+//! server rejects it, at which point the bot logs in again.
+
+use crate::cookie;
+use anyhow::{anyhow, ensure, Context, Result};
+use reqwest::{blocking::Client, header::SET_COOKIE};
+use serde::Deserialize;
+use serde_json::json;
+use std::{fmt, fs, io::Write, path::Path, time::Duration};
+
+/// The cookie holding the session.
+pub const SESSION: &str = "__session";
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+const fn default_path<'a>() -> &'a str {
+    "/api/login"
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+pub struct Credentials<'a> {
+    pub username: &'a str,
+    /// Every `${VAR}` is replaced like in a cookie.
+    pub password: &'a str,
+    /// The login endpoint, relative to `base_url`.
+    #[serde(default = "default_path")]
+    pub path: &'a str,
+    /// Where to keep the session cookie between runs.
+    #[serde(default)]
+    pub store: Option<&'a str>,
+}
+
+impl fmt::Debug for Credentials<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("username", &self.username)
+            .field("password", &"<redacted>")
+            .field("path", &self.path)
+            .field("store", &self.store)
+            .finish()
+    }
+}
+
+/// Logs in to `base_url`, returning the session cookie as `__session=...`.
+pub fn login(base_url: &str, credentials: &Credentials) -> Result<String> {
+    let url = format!("{}{}", base_url.trim_end_matches('/'), credentials.path);
+    let password = cookie::interpolate(credentials.password)?;
+
+    let response = Client::builder()
+        .timeout(TIMEOUT)
+        .build()?
+        .post(&url)
+        .json(&json!({
+            "username": credentials.username,
+            "password": password,
+        }))
+        .send()
+        .with_context(|| format!("failed to reach {}", url))?;
+
+    ensure!(
+        response.status().is_success(),
+        "logging in as {} failed with {}",
+        credentials.username,
+        response.status()
+    );
+
+    response
+        .headers()
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .filter_map(|value| value.split(';').next())
+        .map(str::trim)
+        .find(|pair| {
+            pair.strip_prefix(SESSION)
+                .is_some_and(|rest| rest.starts_with('='))
+        })
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("{} did not set a {} cookie", url, SESSION))
+}
+
+/// The stored session cookie if there is one, otherwise a fresh one.
+pub fn session(base_url: &str, credentials: &Credentials) -> Result<String> {
+    if let Some(store) = credentials.store {
+        let path = Path::new(store);
+
+        if path.exists() {
+            cookie::check_permissions(path)?;
+
+            let cookie = fs::read_to_string(path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+
+            if !cookie.trim().is_empty() {
+                return Ok(cookie.trim().to_string());
+            }
+        }
+    }
+
+    refresh(base_url, credentials)
+}
+
+/// Logs in again, replacing the stored session cookie.
+pub fn refresh(base_url: &str, credentials: &Credentials) -> Result<String> {
+    let cookie = login(base_url, credentials)?;
+
+    if let Some(store) = credentials.store {
+        save(Path::new(store), &cookie)
+            .with_context(|| format!("failed to store the session in {}", store))?;
+    }
+
+    Ok(cookie)
+}
+
+fn save(path: &Path, cookie: &str) -> Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options.open(path)?;
+
+    // `mode` only applies to new files.
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+
+    file.write_all(cookie.as_bytes())?;
+
+    Ok(())
+}
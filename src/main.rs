@@ -1,49 +1,300 @@
-use anyhow::Result;
-use lazy_static::lazy_static;
-use log::{info, warn};
-use polygen_bot::{socket::new_bot, BotData, Config};
-use std::{fs, sync::mpsc::channel};
-
-lazy_static! {
-    static ref CONFIG: String = fs::read_to_string("config.toml").unwrap();
-    static ref BOT_DATA: Vec<BotData> = {
-        (|| -> Result<_> {
-            let config: Config = toml::from_str(&CONFIG)?;
-
-            let mut ans = Vec::new();
-
-            for bot in config.bots {
-                ans.push(BotData {
-                    bot,
-                    room: config.rooms.get(&bot.room).copied(),
-                    base_url: config.base_url,
-                });
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{Parser, Subcommand};
+use log::{debug, error, info, warn, LevelFilter};
+use polygen_bot::{
+    bot::Bot,
+    render,
+    replay::Replay,
+    socket::{needs_restart, new_bot, BotHandle, Status},
+    validate::{validate, Problem, Severity},
+    BotData, Config,
+};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::mpsc::{channel, RecvTimeoutError},
+    time::{Duration, SystemTime},
+};
+
+#[derive(Parser)]
+#[command(version, about = "Bots for polygen")]
+struct Cli {
+    /// The config file.
+    #[arg(short, long, global = true, default_value = "config.toml")]
+    config: PathBuf,
+
+    /// One of off, error, warn, info, debug and trace.
+    #[arg(long, global = true, default_value = "info")]
+    log_level: LevelFilter,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Connect the bots and play until interrupted; the default.
+    Run {
+        /// Only run the bot with this name. Can be repeated.
+        #[arg(long, value_name = "NAME")]
+        only: Vec<String>,
+    },
+    /// Load the config and report every problem in it without connecting.
+    Check,
+    /// Print the settings every bot runs with.
+    List,
+    /// Re-run a recorded game and print every turn where the bot now
+    /// decides differently.
+    Replay {
+        path: PathBuf,
+        /// Draw the map every turn.
+        #[arg(long)]
+        render: bool,
+        /// Wait for Enter after every turn; implies `--render`.
+        #[arg(long)]
+        step: bool,
+    },
+}
+
+/// Parses the config at `path`. The config text is leaked, as the bots
+/// borrow from it for as long as the program runs.
+fn parse(path: &Path) -> Result<Config<'static>> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?
+        .leak();
+
+    toml::from_str(text).with_context(|| format!("failed to parse {}", path.display()))
+}
+
+/// Fails if any of `problems` is an error, listing all of them.
+fn ensure_valid(path: &Path, problems: &[Problem]) -> Result<()> {
+    let errors: Vec<String> = problems
+        .iter()
+        .filter(|problem| problem.severity == Severity::Error)
+        .map(|problem| problem.to_string())
+        .collect();
+
+    if !errors.is_empty() {
+        bail!("invalid {}:\n{}", path.display(), errors.join("\n"));
+    }
+
+    Ok(())
+}
+
+/// Loads and validates the bots from the config at `path`, logging any
+/// warnings.
+fn load(path: &Path) -> Result<&'static [BotData]> {
+    let config = parse(path)?;
+    let problems = validate(&config);
+
+    ensure_valid(path, &problems)?;
+
+    for problem in &problems {
+        warn!("{}: {}", path.display(), problem);
+    }
+
+    let mut ans = Vec::new();
+
+    for bot in config.bots {
+        ans.push(BotData {
+            bot,
+            room: config.rooms.get(&bot.room).copied(),
+            base_url: config.base_url,
+            replay_dir: config.replay_dir,
+        });
+    }
+
+    debug!("{:?}", ans);
+
+    Ok(ans.leak())
+}
+
+/// Re-runs the bot from the config with the same name against a recorded
+/// game, printing every turn where its decision changed. With `render` every
+/// turn is drawn, and with `step` the replay waits for Enter between turns.
+fn replay(config_path: &Path, path: &Path, render: bool, step: bool) -> Result<()> {
+    let replay = Replay::open(path)?;
+
+    let config = load(config_path)?
+        .iter()
+        .find(|bot_data| bot_data.bot.name == replay.bot)
+        .ok_or_else(|| anyhow!("no bot named `{}` in {}", replay.bot, config_path.display()))?;
+
+    let mut bot = Bot::new(config)?;
+    let (mut turns, mut diverged) = (0, 0);
+
+    replay.rerun(&mut bot, |bot, turn| {
+        turns += 1;
+
+        if render {
+            print!(
+                "{}turn {}\n{}",
+                render::CLEAR,
+                turn.index,
+                render::render(bot)
+            );
+        }
+
+        if turn.diverged() {
+            diverged += 1;
+            println!(
+                "turn {}: recorded {:?}, now {:?}",
+                turn.index, turn.recorded, turn.replayed
+            );
+        }
+
+        if step {
+            let _ = io::stdin().lines().next();
+        }
+    });
+
+    println!("{} of {} turns diverged", diverged, turns);
+
+    Ok(())
+}
+
+fn check(config_path: &Path) -> Result<()> {
+    let config = parse(config_path)?;
+    let problems = validate(&config);
+
+    for problem in &problems {
+        println!("{}", problem);
+    }
+
+    let errors = problems
+        .iter()
+        .filter(|problem| problem.severity == Severity::Error)
+        .count();
+
+    if errors > 0 {
+        bail!("{}: {} errors", config_path.display(), errors);
+    }
+
+    println!(
+        "{}: {} bots ok, {} warnings",
+        config_path.display(),
+        config.bots.len(),
+        problems.len()
+    );
+
+    Ok(())
+}
+
+fn list(config_path: &Path) -> Result<()> {
+    for bot_data in load(config_path)? {
+        println!("{:#?}", bot_data);
+    }
+
+    Ok(())
+}
+
+/// When the file at `path` was last modified, if that can be told.
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Brings the running bots in line with a changed config: bots that are
+/// gone are stopped, new ones are started, bots whose connection or
+/// strategy changed or that gave up reconnecting are restarted and the rest
+/// pick up the new settings at their next game. Bots are told apart by name
+/// and room.
+fn reload(config_path: &Path, only: &[String], clients: &mut Vec<BotHandle>) -> Result<()> {
+    let bots = load(config_path)?;
+
+    let selected =
+        |bot_data: &BotData| only.is_empty() || only.iter().any(|name| name == bot_data.bot.name);
+    let same = |a: &BotData, b: &BotData| a.bot.name == b.bot.name && a.bot.room == b.bot.room;
+
+    let mut kept = Vec::new();
+
+    for client in clients.drain(..) {
+        let old = client.config();
+        let new = bots.iter().find(|new| selected(new) && same(old, new));
+
+        match new {
+            Some(new) if !needs_restart(old, new) && client.status() != Status::Failed => {
+                if new != old {
+                    info!("Reloading {}", new.bot.name);
+
+                    if let Err(err) = client.reload(new) {
+                        error!("Failed to reload {}: {:?}", new.bot.name, err);
+                    }
+                }
+
+                kept.push(client);
+                continue;
             }
+            Some(_) => info!("Restarting {}", old.bot.name),
+            None => info!("Stopping {}", old.bot.name),
+        }
+
+        if let Err(err) = client.disconnect() {
+            error!("Failed to disconnect {}: {:?}", old.bot.name, err);
+        }
+    }
+
+    *clients = kept;
 
-            info!("{:?}", ans);
+    for new in bots.iter().filter(|new| selected(new)) {
+        if !clients.iter().any(|client| same(client.config(), new)) {
+            info!("Starting {}", new.bot.name);
 
-            Ok(ans)
-        })()
-        .unwrap()
-    };
+            match new_bot(new) {
+                Ok(client) => clients.push(client),
+                Err(err) => error!("Failed to start {}: {:?}", new.bot.name, err),
+            }
+        }
+    }
+
+    Ok(())
 }
 
-fn main() -> Result<()> {
-    env_logger::builder()
-        .filter_level(log::LevelFilter::Info)
-        .init();
+fn run(config_path: &Path, only: &[String]) -> Result<()> {
+    let bots = load(config_path)?;
+
+    for name in only {
+        if !bots.iter().any(|bot_data| bot_data.bot.name == name) {
+            bail!("no bot named `{}` in {}", name, config_path.display());
+        }
+    }
 
     let mut clients = Vec::new();
 
-    for bot_data in BOT_DATA.iter() {
-        clients.push(new_bot(bot_data)?);
+    for bot_data in bots {
+        if only.is_empty() || only.iter().any(|name| name == bot_data.bot.name) {
+            clients.push(new_bot(bot_data)?);
+        }
     }
 
     let (tx, rx) = channel();
 
     ctrlc::set_handler(move || tx.send(()).expect("Could not send signal on channel."))?;
 
-    rx.recv()?;
+    let mut failed = false;
+    let mut last_modified = modified(config_path);
+
+    while let Err(RecvTimeoutError::Timeout) = rx.recv_timeout(Duration::from_secs(1)) {
+        let now = modified(config_path);
+
+        if now != last_modified {
+            last_modified = now;
+
+            if let Err(err) = reload(config_path, only, &mut clients) {
+                error!("Kept the old config: {:?}", err);
+            }
+        }
+
+        if !clients.is_empty()
+            && clients
+                .iter()
+                .all(|client| client.status() == Status::Failed)
+        {
+            failed = true;
+            break;
+        }
+    }
 
     warn!("Shutting down...");
 
@@ -51,5 +302,22 @@ fn main() -> Result<()> {
         client.disconnect()?;
     }
 
+    if failed {
+        bail!("every bot failed to reconnect");
+    }
+
     Ok(())
 }
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+
+    env_logger::builder().filter_level(cli.log_level).init();
+
+    match cli.command.unwrap_or(Command::Run { only: Vec::new() }) {
+        Command::Run { only } => run(&cli.config, &only),
+        Command::Check => check(&cli.config),
+        Command::List => list(&cli.config),
+        Command::Replay { path, render, step } => replay(&cli.config, &path, render || step, step),
+    }
+}
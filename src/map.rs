@@ -1,5 +1,8 @@
-use serde::Deserialize;
-use std::ops::{Index, IndexMut};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    ops::{Deref, DerefMut, Index, IndexMut},
+};
 
 pub type Pos = (usize, usize);
 
@@ -36,10 +39,13 @@ impl Default for LandType {
     }
 }
 
-#[derive(Deserialize, Clone, Copy)]
+#[derive(Deserialize, Serialize, Clone, Copy)]
 pub struct MaybeLand {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub c: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub t: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub a: Option<i32>,
 }
 
@@ -72,7 +78,14 @@ impl Land {
     }
 }
 
-#[derive(Deserialize, Clone)]
+/// What a tile looked like the last time it was visible.
+#[derive(Clone, Copy)]
+pub struct Sighting {
+    pub land: Land,
+    pub turn: u32,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
 pub struct MaybeMap {
     pub width: usize,
     pub height: usize,
@@ -80,12 +93,75 @@ pub struct MaybeMap {
     pub mode: String,
 }
 
+/// Up to six positions stored inline, as returned by [`Map::neighbours`].
+/// Derefs to a slice, so it can be shuffled and searched in place.
+#[derive(Clone, Copy, Default)]
+pub struct Neighbours {
+    len: u8,
+    items: [Pos; 6],
+}
+
+impl Neighbours {
+    #[inline]
+    fn push(&mut self, pos: Pos) {
+        self.items[self.len as usize] = pos;
+        self.len += 1;
+    }
+
+    #[inline]
+    pub fn retain(&mut self, mut f: impl FnMut(&Pos) -> bool) {
+        let mut len = 0;
+
+        for index in 0..self.len as usize {
+            if f(&self.items[index]) {
+                self.items[len] = self.items[index];
+                len += 1;
+            }
+        }
+
+        self.len = len as u8;
+    }
+}
+
+impl Deref for Neighbours {
+    type Target = [Pos];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.items[..self.len as usize]
+    }
+}
+
+impl DerefMut for Neighbours {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.items[..self.len as usize]
+    }
+}
+
+impl IntoIterator for Neighbours {
+    type Item = Pos;
+    type IntoIter = std::iter::Take<std::array::IntoIter<Pos, 6>>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.into_iter().take(self.len as usize)
+    }
+}
+
+/// The map, stored as a flat array indexed by the 1-based tile ids used in
+/// `Updates`; index 0 is unused. Tiles can be indexed either by id or by
+/// position.
 #[derive(Clone)]
 pub struct Map {
     pub width: usize,
     pub height: usize,
-    pub gm: Vec<Vec<Land>>,
     pub mode: String,
+    tiles: Vec<Land>,
+    /// The last sighting of every tile, filled in by [`Map::remember`].
+    seen: Vec<Option<Sighting>>,
+    /// In-bounds tiles next to every tile, mountains included.
+    adjacency: Vec<Neighbours>,
 }
 
 impl Index<Pos> for Map {
@@ -93,40 +169,131 @@ impl Index<Pos> for Map {
 
     #[inline]
     fn index(&self, index: Pos) -> &Self::Output {
-        &self.gm[index.0][index.1]
+        &self.tiles[self.id(index)]
     }
 }
 
 impl IndexMut<Pos> for Map {
     #[inline]
     fn index_mut(&mut self, index: Pos) -> &mut Self::Output {
-        &mut self.gm[index.0][index.1]
+        let id = self.id(index);
+        &mut self.tiles[id]
     }
 }
 
-impl Map {
+impl Index<usize> for Map {
+    type Output = Land;
+
+    #[inline]
+    fn index(&self, id: usize) -> &Self::Output {
+        &self.tiles[id]
+    }
+}
+
+impl IndexMut<usize> for Map {
     #[inline]
+    fn index_mut(&mut self, id: usize) -> &mut Self::Output {
+        &mut self.tiles[id]
+    }
+}
+
+impl Default for Map {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Map {
     pub fn from(maybe_map: MaybeMap) -> Self {
-        let gm = maybe_map
-            .gm
-            .into_iter()
-            .map(|row| row.into_iter().map(Land::from).collect())
-            .collect();
-        Self {
-            width: maybe_map.width,
-            height: maybe_map.height,
-            gm,
-            mode: maybe_map.mode,
+        let mut gm = Self::with_size(maybe_map.width, maybe_map.height, maybe_map.mode);
+
+        // Row and column 0 of `maybe_map.gm` are padding.
+        for (i, row) in maybe_map.gm.into_iter().enumerate().skip(1) {
+            for (j, maybe_land) in row.into_iter().enumerate().skip(1) {
+                if gm.check((i, j)) {
+                    gm[(i, j)] = Land::from(maybe_land);
+                }
+            }
         }
+
+        gm
     }
 
     #[inline]
     pub fn new() -> Self {
-        Self {
-            mode: "六边形".to_string(),
-            width: 0,
-            height: 0,
-            gm: Vec::new(),
+        Self::with_size(0, 0, "六边形".to_string())
+    }
+
+    /// A map of empty neutral land.
+    pub fn with_size(width: usize, height: usize, mode: String) -> Self {
+        let mut gm = Self {
+            width,
+            height,
+            mode,
+            tiles: vec![Land::default(); width * height + 1],
+            seen: vec![None; width * height + 1],
+            adjacency: vec![Neighbours::default(); width * height + 1],
+        };
+
+        for i in 1..=height {
+            for j in 1..=width {
+                let mut adjacent = Neighbours::default();
+
+                for &(dx, dy) in gm.dir((i, j)) {
+                    let pos = (
+                        i.checked_add_signed(dx as isize),
+                        j.checked_add_signed(dy as isize),
+                    );
+
+                    if let (Some(i), Some(j)) = pos {
+                        if gm.check((i, j)) {
+                            adjacent.push((i, j));
+                        }
+                    }
+                }
+
+                let id = gm.id((i, j));
+                gm.adjacency[id] = adjacent;
+            }
+        }
+
+        gm
+    }
+
+    /// The map as the server sends it in `gameStart`.
+    pub fn maybe_map(&self) -> MaybeMap {
+        let empty = MaybeLand {
+            c: None,
+            t: None,
+            a: None,
+        };
+
+        let gm = (0..=self.height)
+            .map(|i| {
+                (0..=self.width)
+                    .map(|j| {
+                        if self.check((i, j)) {
+                            let land = &self[(i, j)];
+
+                            MaybeLand {
+                                c: Some(land.color),
+                                t: Some(land.r#type as u8),
+                                a: Some(land.amount),
+                            }
+                        } else {
+                            empty
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+
+        MaybeMap {
+            width: self.width,
+            height: self.height,
+            gm,
+            mode: self.mode.clone(),
         }
     }
 
@@ -141,28 +308,139 @@ impl Map {
         land.r#type != LandType::Mountain && land.r#type != LandType::UnknownMountain
     }
 
+    /// Whether `pos` is currently outside the fog of war.
     #[inline]
-    pub fn dir(&self, (_, j): Pos) -> Vec<(i8, i8)> {
+    pub fn visible(&self, pos: Pos) -> bool {
+        !matches!(
+            self[pos].r#type,
+            LandType::Unknown | LandType::UnknownCity | LandType::UnknownMountain
+        )
+    }
+
+    /// Records every visible tile as seen on `turn`.
+    pub fn remember(&mut self, turn: u32) {
+        for id in 1..self.tiles.len() {
+            let land = self.tiles[id];
+
+            if !matches!(
+                land.r#type,
+                LandType::Unknown | LandType::UnknownCity | LandType::UnknownMountain
+            ) {
+                self.seen[id] = Some(Sighting { land, turn });
+            }
+        }
+    }
+
+    #[inline]
+    pub fn last_seen(&self, pos: Pos) -> Option<&Sighting> {
+        self.seen[self.id(pos)].as_ref()
+    }
+
+    /// The tile at `pos`, or how it was last seen if it is hidden now and
+    /// was a general or a city back then.
+    #[inline]
+    pub fn recall(&self, pos: Pos) -> Land {
+        match self.last_seen(pos) {
+            Some(sighting)
+                if !self.visible(pos)
+                    && matches!(sighting.land.r#type, LandType::General | LandType::City) =>
+            {
+                sighting.land
+            }
+            _ => self[pos],
+        }
+    }
+
+    #[inline]
+    pub fn dir(&self, (_, j): Pos) -> &'static [(i8, i8)] {
         match self.mode.as_str() {
-            "六边形" if j % 2 == 1 => vec![(-1, -1), (-1, 0), (-1, 1), (0, 1), (1, 0), (0, -1)],
-            "六边形" => vec![(0, -1), (-1, 0), (0, 1), (1, 1), (1, 0), (1, -1)],
-            _ => vec![(-1, 0), (0, -1), (1, 0), (0, 1)],
+            "六边形" if j % 2 == 1 => &[(-1, -1), (-1, 0), (-1, 1), (0, 1), (1, 0), (0, -1)],
+            "六边形" => &[(0, -1), (-1, 0), (0, 1), (1, 1), (1, 0), (1, -1)],
+            _ => &[(-1, 0), (0, -1), (1, 0), (0, 1)],
         }
     }
 
+    /// All in-bounds tiles next to `pos`, mountains included.
     #[inline]
-    pub fn neighbours(&self, (i, j): Pos) -> Vec<Pos> {
-        self.dir((i, j))
-            .into_iter()
-            .map(|(dx, dy)| ((i as i8 + dx) as usize, (j as i8 + dy) as usize))
-            .filter(|&pos| self.check(pos) && self.accessible(pos))
-            .collect()
+    pub fn adjacent(&self, pos: Pos) -> Neighbours {
+        self.adjacency[self.id(pos)]
+    }
+
+    #[inline]
+    pub fn neighbours(&self, pos: Pos) -> Neighbours {
+        let mut res = self.adjacent(pos);
+        res.retain(|&pos| self.accessible(pos));
+        res
+    }
+
+    /// One `value` per tile, indexed by tile id like the map itself.
+    #[inline]
+    pub fn grid<T: Clone>(&self, value: T) -> Vec<T> {
+        vec![value; self.tiles.len()]
+    }
+
+    /// BFS distances over accessible tiles from the nearest of `sources`,
+    /// searching at most `limit` steps out, indexed by tile id.
+    pub fn distances(
+        &self,
+        sources: impl IntoIterator<Item = Pos>,
+        limit: u32,
+    ) -> Vec<Option<u32>> {
+        let mut dist = self.grid(None);
+        let mut q = VecDeque::new();
+
+        for pos in sources {
+            dist[self.id(pos)] = Some(0);
+            q.push_back(pos);
+        }
+
+        while let Some(cur) = q.pop_front() {
+            let d = dist[self.id(cur)].unwrap();
+
+            if d == limit {
+                continue;
+            }
+
+            for nxt in self.neighbours(cur) {
+                let id = self.id(nxt);
+
+                if dist[id].is_none() {
+                    dist[id] = Some(d + 1);
+                    q.push_back(nxt);
+                }
+            }
+        }
+
+        dist
+    }
+
+    /// Converts a 1-based tile id, as used in `Updates`, to a position.
+    #[inline]
+    pub const fn pos(&self, id: usize) -> Pos {
+        let y = (id - 1) % self.width + 1;
+        let x = (id - y) / self.width + 1;
+        (x, y)
+    }
+
+    /// Converts a position to the 1-based tile id used in `Updates`.
+    #[inline]
+    pub const fn id(&self, (i, j): Pos) -> usize {
+        (i - 1) * self.width + j
+    }
+
+    #[inline]
+    fn positions(&self) -> impl Iterator<Item = Pos> {
+        let width = self.width;
+        (1..=self.height).flat_map(move |i| (1..=width).map(move |j| (i, j)))
     }
 
     #[inline]
     pub fn iter(&self) -> impl IntoIterator<Item = (Pos, &Land)> + '_ {
-        (1..=self.height)
-            .flat_map(|x| (1..=self.width).map(move |y| (x, y)))
-            .map(|pos| (pos, &self[pos]))
+        self.positions().zip(&self.tiles[1..])
+    }
+
+    #[inline]
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Pos, &mut Land)> + '_ {
+        self.positions().zip(&mut self.tiles[1..])
     }
 }
@@ -0,0 +1,406 @@
+//! A stand-in polygen server for exercising [`crate::socket::new_bot`]
+//! without a network.
+//!
+//! The server speaks just enough engine.io v4 and socket.io v5 for the
+//! client the bot uses: the polling handshake, the upgrade to websocket and
+//! events on the default namespace. Tests push server events with
+//! [`MockServer::emit`] and its helpers, react to bot events with
+//! [`MockServer::on`], and assert on what the bot emitted with
+//! [`MockServer::wait_for`]. With [`MockServer::require_login`] it also
+//! stands in for the login endpoint used by [`crate::login`].
+
+use crate::event::{GameStart, Patch, UpdateTeams};
+use anyhow::{bail, Result};
+use parking_lot::{Condvar, Mutex};
+use serde_json::{json, Value};
+use std::{
+    collections::VecDeque,
+    io::{ErrorKind, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        mpsc::{channel, Receiver, Sender},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use tungstenite::{Message, WebSocket};
+
+/// How often connection threads look for frames queued by [`MockServer::emit`].
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Queued instead of a frame to close a connection without a word.
+const HANG_UP: &str = "";
+
+type Handler = Box<dyn FnMut(&Value) -> Vec<(String, Value)> + Send>;
+
+/// The only account [`MockServer`] accepts, see [`MockServer::require_login`].
+struct Account {
+    username: String,
+    password: String,
+    /// Session cookies handed out so far, as `__session=...`.
+    sessions: Vec<String>,
+}
+
+#[derive(Default)]
+struct Shared {
+    received: Mutex<VecDeque<(String, Value)>>,
+    arrived: Condvar,
+    connections: Mutex<Vec<Sender<String>>>,
+    handlers: Mutex<Vec<(String, Handler)>>,
+    cookies: Mutex<Vec<String>>,
+    account: Mutex<Option<Account>>,
+    next_sid: AtomicU64,
+    /// Handshakes still to be hung up on, see [`MockServer::hang_up_handshakes`].
+    hang_ups: AtomicUsize,
+}
+
+pub struct MockServer {
+    addr: SocketAddr,
+    shared: Arc<Shared>,
+}
+
+#[inline]
+fn frame(event: &str, data: &Value) -> String {
+    format!("42{}", json!([event, data]))
+}
+
+fn header<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+    head.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.trim()
+            .eq_ignore_ascii_case(name)
+            .then_some(value.trim())
+    })
+}
+
+impl MockServer {
+    /// Starts listening on a free local port.
+    pub fn start() -> Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let shared = Arc::new(Shared::default());
+
+        let accepting = shared.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let shared = accepting.clone();
+                thread::spawn(move || {
+                    if let Err(err) = Self::serve(stream, &shared) {
+                        debug!("mock connection closed: {:?}", err);
+                    }
+                });
+            }
+        });
+
+        Ok(Self { addr, shared })
+    }
+
+    /// The address to use as `base_url`.
+    #[inline]
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Every `cookie` header sent with a handshake so far.
+    pub fn cookies(&self) -> Vec<String> {
+        self.shared.cookies.lock().clone()
+    }
+
+    /// From now on, rejects handshakes unless their cookie came from logging
+    /// in as `username` with `password` at `POST /api/login`.
+    pub fn require_login(&self, username: &str, password: &str) {
+        *self.shared.account.lock() = Some(Account {
+            username: username.to_string(),
+            password: password.to_string(),
+            sessions: Vec::new(),
+        });
+    }
+
+    /// Forgets every session handed out so far, as if they had expired.
+    pub fn expire_sessions(&self) {
+        if let Some(account) = self.shared.account.lock().as_mut() {
+            account.sessions.clear();
+        }
+    }
+
+    /// Answers every later `event` from a bot with the events `handler`
+    /// returns, sent back to the same bot.
+    pub fn on<F>(&self, event: &str, handler: F)
+    where
+        F: FnMut(&Value) -> Vec<(String, Value)> + Send + 'static,
+    {
+        self.shared
+            .handlers
+            .lock()
+            .push((event.to_string(), Box::new(handler)));
+    }
+
+    /// Sends `event` to every connected bot.
+    pub fn emit(&self, event: &str, data: Value) {
+        let frame = frame(event, &data);
+
+        self.shared
+            .connections
+            .lock()
+            .retain(|connection| connection.send(frame.clone()).is_ok());
+    }
+
+    pub fn emit_game_start(&self, game_start: &GameStart) -> Result<()> {
+        self.emit("gameStart", serde_json::to_value(game_start)?);
+        Ok(())
+    }
+
+    /// Sends a patch compressed the way the real server does.
+    pub fn emit_patch(&self, patch: &Patch) -> Result<()> {
+        let compressed = lz_str::compress_to_utf16(&serde_json::to_string(patch)?);
+        self.emit("patch", Value::String(compressed));
+        Ok(())
+    }
+
+    pub fn emit_update_teams(&self, teams: &UpdateTeams) -> Result<()> {
+        self.emit("updateTeams", serde_json::to_value(teams)?);
+        Ok(())
+    }
+
+    pub fn emit_win(&self, winner: &str) {
+        self.emit("win", json!(winner));
+    }
+
+    /// Kicks every bot with a socket.io disconnect packet.
+    pub fn disconnect_all(&self) {
+        self.shared
+            .connections
+            .lock()
+            .drain(..)
+            .for_each(|connection| {
+                let _ = connection.send("41".to_string());
+            });
+    }
+
+    /// Closes every bot's connection without a disconnect packet, the way
+    /// a network failure would.
+    pub fn hang_up_all(&self) {
+        self.shared
+            .connections
+            .lock()
+            .drain(..)
+            .for_each(|connection| {
+                let _ = connection.send(HANG_UP.to_string());
+            });
+    }
+
+    /// Hangs up on the next `count` bots that connect instead of
+    /// confirming their connection, so that they never see `open`.
+    pub fn hang_up_handshakes(&self, count: usize) {
+        self.shared.hang_ups.store(count, Ordering::Relaxed);
+    }
+
+    /// Waits for a bot to emit `event`, returning its payload. Earlier
+    /// events of other kinds are left for later calls.
+    pub fn wait_for(&self, event: &str, timeout: Duration) -> Option<Value> {
+        let deadline = Instant::now() + timeout;
+        let mut received = self.shared.received.lock();
+
+        loop {
+            if let Some(index) = received.iter().position(|(name, _)| name == event) {
+                return received.remove(index).map(|(_, data)| data);
+            }
+
+            if self
+                .shared
+                .arrived
+                .wait_until(&mut received, deadline)
+                .timed_out()
+            {
+                return None;
+            }
+        }
+    }
+
+    /// Everything bots have emitted and nobody waited for yet, oldest first.
+    pub fn received(&self) -> Vec<(String, Value)> {
+        self.shared.received.lock().iter().cloned().collect()
+    }
+
+    fn serve(mut stream: TcpStream, shared: &Shared) -> Result<()> {
+        let mut buf = [0; 4096];
+        let len = stream.peek(&mut buf)?;
+        let head = String::from_utf8_lossy(&buf[..len]).into_owned();
+
+        if head.contains("transport=websocket") {
+            let socket = tungstenite::accept(stream)?;
+            return Self::serve_websocket(socket, shared);
+        }
+
+        if head.starts_with("POST ") {
+            return Self::serve_login(stream, shared);
+        }
+
+        // Consume the request we peeked at and answer the polling handshake.
+        let _ = stream.read(&mut buf)?;
+
+        let cookie = header(&head, "cookie").unwrap_or_default();
+        shared.cookies.lock().push(cookie.to_string());
+
+        if let Some(account) = shared.account.lock().as_ref() {
+            if !account.sessions.iter().any(|session| session == cookie) {
+                write!(
+                    stream,
+                    "HTTP/1.1 401 Unauthorized\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                )?;
+                return Ok(());
+            }
+        }
+
+        let sid = shared.next_sid.fetch_add(1, Ordering::Relaxed);
+        let body = format!(
+            r#"0{{"sid":"{}","upgrades":["websocket"],"pingInterval":25000,"pingTimeout":20000}}"#,
+            sid
+        );
+
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=UTF-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        )?;
+
+        Ok(())
+    }
+
+    fn serve_login(mut stream: TcpStream, shared: &Shared) -> Result<()> {
+        let mut request = Vec::new();
+        let mut buf = [0; 4096];
+
+        // Read until the headers and the body they announce are in.
+        let (head, body) = loop {
+            let len = stream.read(&mut buf)?;
+            if len == 0 {
+                bail!("login request cut short");
+            }
+            request.extend_from_slice(&buf[..len]);
+
+            let text = String::from_utf8_lossy(&request).into_owned();
+            if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                let length = header(head, "content-length")
+                    .and_then(|length| length.parse().ok())
+                    .unwrap_or(0);
+
+                if body.len() >= length {
+                    break (head.to_string(), body.to_string());
+                }
+            }
+        };
+
+        let credentials: Value = serde_json::from_str(&body).unwrap_or_default();
+        let mut account = shared.account.lock();
+
+        let session = account.as_mut().filter(|account| {
+            head.starts_with("POST /api/login ")
+                && credentials["username"] == account.username.as_str()
+                && credentials["password"] == account.password.as_str()
+        });
+
+        match session {
+            Some(account) => {
+                let session = format!(
+                    "__session=mock-{}",
+                    shared.next_sid.fetch_add(1, Ordering::Relaxed)
+                );
+                account.sessions.push(session.clone());
+
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nSet-Cookie: {}; Path=/; HttpOnly\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{{}}",
+                    session
+                )?;
+            }
+            None => write!(
+                stream,
+                "HTTP/1.1 401 Unauthorized\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+            )?,
+        }
+
+        Ok(())
+    }
+
+    fn serve_websocket(mut socket: WebSocket<TcpStream>, shared: &Shared) -> Result<()> {
+        socket.get_mut().set_read_timeout(Some(POLL_INTERVAL))?;
+
+        let (tx, rx): (Sender<String>, Receiver<String>) = channel();
+        let mut registered = Some(tx);
+
+        loop {
+            for frame in rx.try_iter() {
+                if frame == HANG_UP {
+                    return Ok(());
+                }
+
+                socket.write_message(Message::text(frame))?;
+            }
+
+            let text = match socket.read_message() {
+                Ok(Message::Text(text)) => text,
+                Ok(Message::Close(_)) => return Ok(()),
+                Ok(_) => continue,
+                Err(tungstenite::Error::Io(err))
+                    if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) =>
+                {
+                    continue
+                }
+                Err(err) => return Err(err.into()),
+            };
+
+            match text.as_str() {
+                "2probe" => socket.write_message(Message::text("3probe"))?,
+                "2" => socket.write_message(Message::text("3"))?,
+                "5" | "3" => {}
+                "41" => return Ok(()),
+                _ if text.starts_with("40") => {
+                    let hang_up = shared
+                        .hang_ups
+                        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |count| {
+                            count.checked_sub(1)
+                        })
+                        .is_ok();
+
+                    if hang_up {
+                        return Ok(());
+                    }
+
+                    socket.write_message(Message::text(r#"40{"sid":"mock"}"#))?;
+
+                    if let Some(tx) = registered.take() {
+                        shared.connections.lock().push(tx);
+                    }
+                }
+                _ if text.starts_with("42") => {
+                    let Value::Array(mut args) = serde_json::from_str(&text[2..])? else {
+                        bail!("malformed event {}", text);
+                    };
+
+                    if args.is_empty() {
+                        bail!("event without a name: {}", text);
+                    }
+
+                    let event = args.remove(0).as_str().unwrap_or_default().to_string();
+                    let data = args.into_iter().next().unwrap_or(Value::Null);
+
+                    for (name, handler) in shared.handlers.lock().iter_mut() {
+                        if *name == event {
+                            for (reply, data) in handler(&data) {
+                                socket.write_message(Message::text(frame(&reply, &data)))?;
+                            }
+                        }
+                    }
+
+                    shared.received.lock().push_back((event, data));
+                    shared.arrived.notify_all();
+                }
+                _ => debug!("mock server ignored {}", text),
+            }
+        }
+    }
+}
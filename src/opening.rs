@@ -0,0 +1,381 @@
+//! Scripted openings, played from `gameStart` until `opening_turns` before
+//! the configured strategy takes over.
+//!
+//! The `waves` opening only grabs neutral land, in waves: wait until the
+//! general holds enough armies, walk them through our land to its edge and
+//! keep capturing one new tile per turn until the army runs out. The launch
+//! thresholds of the first and of later waves are chosen by simulating every
+//! combination on the start map and keeping the one that owns the most land
+//! at the last opening turn, skipping thresholds too high to launch in time.
+//! Tiles hidden by fog are assumed to be plain land; the opening gives up as
+//! soon as a move turns out to be impossible.
+
+use crate::{
+    bot::Movement,
+    map::{LandType, Map, Pos},
+};
+use std::collections::VecDeque;
+
+/// The most turns an opening may last. Planning `waves` happens at
+/// `gameStart` and takes far longer than a turn beyond this.
+pub const MAX_TURNS: u32 = 50;
+
+/// Openings by the name used for `opening` in `config.toml`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Book {
+    None,
+    Waves,
+}
+
+impl Book {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "none" => Some(Self::None),
+            "waves" => Some(Self::Waves),
+            _ => None,
+        }
+    }
+
+    /// Plans the opening for the start map, or `None` if there is nothing
+    /// to play.
+    pub fn plan(self, gm: &Map, my_color: u8, until: u32) -> Option<Opening> {
+        let general = gm.iter().into_iter().find_map(|(pos, land)| {
+            (land.r#type == LandType::General && land.color == my_color).then_some(pos)
+        })?;
+
+        match self {
+            Self::None => None,
+            Self::Waves => Some(Opening::waves(gm, general, until)),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Wave {
+    /// Armies the general must hold before the wave sets off.
+    threshold: i32,
+    /// Every tile the army visits, starting at the general.
+    path: Vec<Pos>,
+}
+
+pub struct Opening {
+    until: u32,
+    waves: Vec<Wave>,
+    /// Index of the wave being played.
+    wave: usize,
+    /// Index in the wave's path of the tile the army is on; 0 until it
+    /// leaves the general.
+    step: usize,
+}
+
+#[inline]
+fn capturable(gm: &Map, pos: Pos) -> bool {
+    let land = &gm[pos];
+
+    match land.r#type {
+        LandType::Unknown => true,
+        LandType::Land => land.color == 0 && land.amount == 0,
+        _ => false,
+    }
+}
+
+/// A set of tiles, as a bitset indexed by tile id.
+#[derive(Clone)]
+struct Tiles(Vec<u64>);
+
+impl Tiles {
+    fn new(gm: &Map) -> Self {
+        Self(vec![0; gm.width * gm.height / 64 + 1])
+    }
+
+    #[inline]
+    fn contains(&self, id: usize) -> bool {
+        self.0[id / 64] & 1 << (id % 64) != 0
+    }
+
+    /// Adds `id`, returning whether it was not in the set yet.
+    #[inline]
+    fn insert(&mut self, id: usize) -> bool {
+        let new = !self.contains(id);
+        self.0[id / 64] |= 1 << (id % 64);
+        new
+    }
+}
+
+/// What the search for wave paths needs, kept between searches so that
+/// trying every launch threshold does not allocate per candidate.
+struct Planner<'a> {
+    gm: &'a Map,
+    general: Pos,
+    /// Distances from the general, which waves try to get away from.
+    dist: Vec<Option<u32>>,
+    parent: Vec<Option<Pos>>,
+    /// Steps from the general through owned tiles.
+    depth: Vec<usize>,
+    /// Owned tiles reachable from the general, in map order.
+    inside: Vec<Pos>,
+    q: VecDeque<Pos>,
+    taken: Tiles,
+    tail: Vec<Pos>,
+}
+
+impl<'a> Planner<'a> {
+    fn new(gm: &'a Map, general: Pos) -> Self {
+        Self {
+            gm,
+            general,
+            dist: gm.distances([general], u32::MAX),
+            parent: gm.grid(None),
+            depth: gm.grid(0),
+            inside: Vec::new(),
+            q: VecDeque::new(),
+            taken: Tiles::new(gm),
+            tail: Vec::new(),
+        }
+    }
+
+    #[inline]
+    fn free(&self, pos: Pos, taken: &Tiles) -> bool {
+        !taken.contains(self.gm.id(pos)) && capturable(self.gm, pos)
+    }
+
+    /// A path for an army of `armies` leaving the general: through `owned`
+    /// tiles to the nearest edge from which it can capture the most new
+    /// tiles.
+    fn wave_path(&mut self, owned: &Tiles, armies: i32) -> Vec<Pos> {
+        let (gm, general) = (self.gm, self.general);
+
+        for &pos in &self.inside {
+            self.parent[gm.id(pos)] = None;
+        }
+        self.inside.clear();
+
+        self.q.push_back(general);
+        self.parent[gm.id(general)] = Some(general);
+
+        while let Some(cur) = self.q.pop_front() {
+            self.inside.push(cur);
+
+            for nxt in gm.neighbours(cur) {
+                let id = gm.id(nxt);
+
+                if owned.contains(id) && self.parent[id].is_none() {
+                    self.parent[id] = Some(cur);
+                    self.depth[id] = self.depth[gm.id(cur)] + 1;
+                    self.q.push_back(nxt);
+                }
+            }
+        }
+
+        self.inside.sort_unstable();
+
+        let mut best: Vec<Pos> = vec![general];
+        let mut best_key = (0, 0);
+        let mut taken = std::mem::replace(&mut self.taken, Tiles(Vec::new()));
+        let mut tail = std::mem::take(&mut self.tail);
+
+        for index in 0..self.inside.len() {
+            let edge = self.inside[index];
+
+            // Tiles with nothing to capture next to them cannot be the edge.
+            if !gm.neighbours(edge).iter().any(|&pos| self.free(pos, owned)) {
+                continue;
+            }
+
+            taken.0.clone_from(&owned.0);
+            tail.clear();
+            let mut army = armies - 1;
+            let mut cur = edge;
+
+            while army > 0 {
+                // Prefer tiles that leave room to keep going, then tiles far
+                // from the general.
+                let Some(nxt) = gm
+                    .neighbours(cur)
+                    .into_iter()
+                    .filter(|&pos| self.free(pos, &taken))
+                    .max_by_key(|&pos| {
+                        let room = gm
+                            .neighbours(pos)
+                            .into_iter()
+                            .filter(|&pos| self.free(pos, &taken))
+                            .count();
+                        (room.min(1), self.dist[gm.id(pos)])
+                    })
+                else {
+                    break;
+                };
+
+                taken.insert(gm.id(nxt));
+                tail.push(nxt);
+                cur = nxt;
+                army -= 1;
+            }
+
+            // Most new tiles first, then the shortest walk to get there.
+            let key = (tail.len(), usize::MAX - self.depth[gm.id(edge)]);
+            if !tail.is_empty() && key > best_key {
+                best_key = key;
+                best = self.walk_to(edge);
+                best.extend_from_slice(&tail);
+            }
+        }
+
+        self.taken = taken;
+        self.tail = tail;
+
+        best
+    }
+
+    /// The path from the general to `edge` through the owned tiles.
+    fn walk_to(&self, edge: Pos) -> Vec<Pos> {
+        let mut path = vec![edge];
+        let mut pos = edge;
+        while pos != self.general {
+            pos = self.parent[self.gm.id(pos)].unwrap();
+            path.push(pos);
+        }
+        path.reverse();
+        path
+    }
+}
+
+/// Where a simulated opening stands after the waves launched so far.
+#[derive(Clone)]
+struct State {
+    owned: Tiles,
+    land: usize,
+    turn: u32,
+    armies: i32,
+    waves: Vec<Wave>,
+}
+
+impl State {
+    fn new(gm: &Map, general: Pos) -> Self {
+        let mut owned = Tiles::new(gm);
+        owned.insert(gm.id(general));
+
+        Self {
+            owned,
+            land: 1,
+            turn: 0,
+            armies: gm[general].amount,
+            waves: Vec::new(),
+        }
+    }
+
+    /// Waits for `threshold` armies and plays a wave with them, returning
+    /// whether it could start before turn `until`. A higher threshold can
+    /// only start later, so once this fails it fails for every higher one.
+    fn launch(&mut self, planner: &mut Planner, until: u32, threshold: i32) -> bool {
+        // The general grows by one army a turn.
+        let turn = self.turn + (threshold - self.armies).max(0) as u32;
+
+        if turn >= until {
+            return false;
+        }
+
+        let armies = self.armies.max(threshold);
+        let mut path = planner.wave_path(&self.owned, armies);
+        path.truncate((until - turn) as usize + 1);
+
+        if path.len() < 2 {
+            return false;
+        }
+
+        let steps = path.len() as u32 - 1;
+
+        for &pos in &path {
+            if self.owned.insert(planner.gm.id(pos)) {
+                self.land += 1;
+            }
+        }
+
+        self.turn = turn + steps;
+        self.armies = 1 + steps as i32;
+        self.waves.push(Wave { threshold, path });
+
+        true
+    }
+}
+
+impl Opening {
+    pub fn waves(gm: &Map, general: Pos, until: u32) -> Self {
+        let max = until as i32 + gm[general].amount;
+        let mut planner = Planner::new(gm, general);
+
+        let mut best = State::new(gm, general);
+
+        for first in 2..=max {
+            let mut start = State::new(gm, general);
+
+            if !start.launch(&mut planner, until, first) {
+                break;
+            }
+
+            for later in 2..=max {
+                let mut plan = start.clone();
+                let mut launched = false;
+
+                while plan.launch(&mut planner, until, later) {
+                    launched = true;
+                }
+
+                if plan.land > best.land {
+                    best = plan;
+                }
+
+                if !launched {
+                    break;
+                }
+            }
+        }
+
+        Self {
+            until,
+            waves: best.waves,
+            wave: 0,
+            step: 0,
+        }
+    }
+
+    /// The opening's move for `turn`: `Some(None)` to wait for the general
+    /// to grow, `Some(Some(movement))` to move, and `None` once the opening
+    /// is over or can no longer be played.
+    pub fn next_move(&mut self, gm: &Map, my_color: u8, turn: u32) -> Option<Option<Movement>> {
+        if turn > self.until {
+            return None;
+        }
+
+        let wave = self.waves.get(self.wave)?;
+        let from = wave.path[self.step];
+
+        if self.step == 0 && gm[from].amount < wave.threshold {
+            return Some(None);
+        }
+
+        let to = wave.path[self.step + 1];
+        let (from_land, to_land) = (&gm[from], &gm[to]);
+
+        let possible = from_land.color == my_color
+            && from_land.amount > 1
+            && (to_land.color == my_color
+                || to_land.color == 0
+                    && to_land.amount < from_land.amount - 1
+                    && matches!(to_land.r#type, LandType::Land));
+
+        if !possible {
+            self.waves.clear();
+            return None;
+        }
+
+        self.step += 1;
+
+        if self.step + 1 == wave.path.len() {
+            self.wave += 1;
+            self.step = 0;
+        }
+
+        Some(Some((from, to, false)))
+    }
+}
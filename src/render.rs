@@ -0,0 +1,125 @@
+//! ANSI-coloured dumps of what a [`Bot`] currently believes the map to be.
+//!
+//! Every tile is drawn as a [`CELL_WIDTH`]-wide cell: a marker followed by
+//! the tile's contents. Markers are `T` for the strategy's target, `F` for
+//! the army it is moving, `o` / `x` for the origin and destination of the
+//! last chosen movement and `.` for the rest of the planned path. In hexagon
+//! mode odd columns sit half a row higher than even ones, matching the
+//! neighbours returned by `Map::dir`.
+
+use crate::{
+    bot::Bot,
+    map::{Land, LandType, Pos},
+};
+use std::fmt::Write;
+
+pub const CELL_WIDTH: usize = 6;
+
+/// Clears the terminal and moves the cursor home, for live redraws.
+pub const CLEAR: &str = "\x1b[H\x1b[2J";
+
+const RESET: &str = "\x1b[0m";
+
+/// Background colors for players, indexed by color; 0 is neutral.
+const PALETTE: [u8; 13] = [250, 160, 27, 34, 178, 127, 37, 208, 91, 100, 166, 61, 131];
+
+const FOG: u8 = 238;
+
+fn style(land: &Land) -> String {
+    let background = match land.r#type {
+        LandType::Unknown | LandType::UnknownCity | LandType::UnknownMountain => FOG,
+        LandType::Mountain => 240,
+        _ => PALETTE[land.color as usize % PALETTE.len()],
+    };
+
+    let foreground = if land.color == 0 { 16 } else { 231 };
+
+    format!("\x1b[48;5;{}m\x1b[38;5;{}m", background, foreground)
+}
+
+fn content(land: &Land) -> String {
+    match land.r#type {
+        LandType::Unknown => String::new(),
+        LandType::UnknownCity => "C?".to_string(),
+        LandType::UnknownMountain => "^?".to_string(),
+        LandType::Mountain => "^^".to_string(),
+        LandType::General => format!("G{}", land.amount),
+        LandType::City => format!("C{}", land.amount),
+        LandType::Land if land.color == 0 && land.amount == 0 => String::new(),
+        LandType::Land => land.amount.to_string(),
+    }
+}
+
+fn marker(bot: &Bot, pos: Pos) -> char {
+    match bot.last_move() {
+        _ if bot.target() == Some(pos) => 'T',
+        _ if bot.from() == Some(pos) => 'F',
+        Some((from, _, _)) if from == pos => 'o',
+        Some((_, to, _)) if to == pos => 'x',
+        _ if bot.plan().contains(&pos) => '.',
+        _ => ' ',
+    }
+}
+
+fn cell(bot: &Bot, pos: Pos, filler: bool) -> String {
+    let land = &bot.gm[pos];
+
+    let text = if filler {
+        String::new()
+    } else {
+        format!("{}{}", marker(bot, pos), content(land))
+    };
+
+    format!(
+        "{}{:<width$.width$}{}",
+        style(land),
+        text,
+        RESET,
+        width = CELL_WIDTH
+    )
+}
+
+/// Renders the bot's view of the map, preceded by a one-line summary.
+pub fn render(bot: &Bot) -> String {
+    let gm = &bot.gm;
+    let mut out = String::new();
+
+    let _ = writeln!(
+        out,
+        "{} (color {})  turn {}  target {:?}  from {:?}  last move {:?}",
+        bot.config().bot.name,
+        bot.my_color,
+        bot.turn(),
+        bot.target(),
+        bot.from(),
+        bot.last_move()
+    );
+
+    if gm.mode != "六边形" {
+        for i in 1..=gm.height {
+            for j in 1..=gm.width {
+                out.push_str(&cell(bot, (i, j), false));
+            }
+            out.push('\n');
+        }
+
+        return out;
+    }
+
+    // Each tile is two lines tall; odd columns start one line above even ones.
+    for line in 0..=gm.height * 2 {
+        for j in 1..=gm.width {
+            let offset = usize::from(j % 2 == 0);
+
+            match line.checked_sub(offset) {
+                Some(k) if k / 2 < gm.height => {
+                    out.push_str(&cell(bot, (k / 2 + 1, j), k % 2 == 1));
+                }
+                _ => out.push_str(&" ".repeat(CELL_WIDTH)),
+            }
+        }
+        out.push('\n');
+    }
+
+    out
+}
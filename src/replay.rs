@@ -0,0 +1,305 @@
+//! Game recordings.
+//!
+//! Every game is written to its own file in the configured `replay_dir`,
+//! named `<unix ms>-<room>-<bot>.jsonl`. The file is JSON Lines: one
+//! [`Entry`] per line, each carrying `t`, the milliseconds since the game
+//! started, and a `type` tag selecting one of the [`Record`] variants:
+//!
+//! ```text
+//! {"t":0,"type":"header","version":5,"bot":"Bot","room":"161","started_at":1700000000000,"game":4}
+//! {"t":0,"type":"game_start","maybeMap":{...},"myColor":2}
+//! {"t":0,"type":"teams","teams":[[1,["Bot"]],[2,["Bot_2"]]]}
+//! {"t":512,"type":"patch","updates":[[12,{"c":2,"a":1}]],"rank":[...]}
+//! {"t":514,"type":"move","movement":[[1,2],[1,3],false],"timed_out":37}
+//! {"t":90210,"type":"win","winner":"Bot"}
+//! ```
+//!
+//! The header always comes first. `game_start`, `patch` and `teams` hold the
+//! payloads exactly as the server sent them (patches after decompression),
+//! `teams` is also written right after `game_start`, and every `patch` is
+//! followed by the `move` that answered it, with a `null` movement if the
+//! bot passed. A `stale` patch was applied without choosing a
+//! move because a newer one had already arrived; see [`crate::worker`].
+//! [`VERSION`] is bumped whenever a change would stop older readers from
+//! understanding a file.
+//!
+//! | version | changes                                     |
+//! |---------|---------------------------------------------|
+//! | 1       | initial format                              |
+//! | 2       | added the `teams` type                      |
+//! | 3       | added `game`, the id the RNG was seeded for |
+//! | 4       | added the `stale` type                      |
+//! | 5       | added `timed_out`; passes are recorded too  |
+
+use crate::{
+    bot::{Bot, Movement},
+    event::{GameStart, Patch, UpdateTeams},
+    BotData,
+};
+use anyhow::{bail, ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+pub const VERSION: u32 = 5;
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Record {
+    Header {
+        version: u32,
+        bot: String,
+        room: String,
+        started_at: u64,
+        /// Counts the games played by the bot since it started; see
+        /// [`Bot::reseed`]. Missing before version 3.
+        #[serde(default)]
+        game: u64,
+    },
+    GameStart(GameStart),
+    Patch(Patch),
+    Stale(Patch),
+    Teams {
+        teams: UpdateTeams,
+    },
+    Move {
+        movement: Option<Movement>,
+        /// When the bot ran out of time choosing the move, so that replays
+        /// can stop at the same point; see [`Bot::timed_out`]. Missing
+        /// before version 5, when replays search `calc_cnt` rounds.
+        #[serde(default)]
+        timed_out: Option<u32>,
+    },
+    Win {
+        winner: String,
+    },
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct Entry {
+    pub t: u64,
+    #[serde(flatten)]
+    pub record: Record,
+}
+
+pub struct Recorder {
+    out: BufWriter<File>,
+    path: PathBuf,
+    started: Instant,
+}
+
+impl Recorder {
+    /// Starts a new recording of game `game` in `dir`, creating the
+    /// directory if needed.
+    pub fn create(dir: impl AsRef<Path>, config: &BotData, game: u64) -> Result<Self> {
+        let started_at = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64;
+
+        let sanitize = |name: &str| -> String {
+            name.chars()
+                .map(|c| if c.is_alphanumeric() { c } else { '_' })
+                .collect()
+        };
+
+        fs::create_dir_all(&dir)?;
+
+        let path = dir.as_ref().join(format!(
+            "{}-{}-{}.jsonl",
+            started_at,
+            sanitize(config.bot.room),
+            sanitize(config.bot.name)
+        ));
+
+        let mut recorder = Self {
+            out: BufWriter::new(File::create(&path)?),
+            path,
+            started: Instant::now(),
+        };
+
+        recorder.record(Record::Header {
+            version: VERSION,
+            bot: config.bot.name.to_string(),
+            room: config.bot.room.to_string(),
+            started_at,
+            game,
+        })?;
+
+        Ok(recorder)
+    }
+
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn record(&mut self, record: Record) -> Result<()> {
+        let entry = Entry {
+            t: self.started.elapsed().as_millis() as u64,
+            record,
+        };
+
+        serde_json::to_writer(&mut self.out, &entry)?;
+        self.out.write_all(b"\n")?;
+        self.out.flush()?;
+
+        Ok(())
+    }
+}
+
+/// A recording read back from disk.
+pub struct Replay {
+    pub version: u32,
+    pub bot: String,
+    pub room: String,
+    pub entries: Vec<Entry>,
+}
+
+/// The bot's decision for one `patch` of a replay, before and now.
+pub struct Turn {
+    pub index: usize,
+    pub recorded: Option<Movement>,
+    pub replayed: Option<Movement>,
+}
+
+impl Turn {
+    #[inline]
+    pub fn diverged(&self) -> bool {
+        self.recorded != self.replayed
+    }
+}
+
+impl Replay {
+    /// When the bot ran out of time choosing the move that answered the
+    /// patch at `index` in `entries`, as recorded with the move.
+    fn timed_out(&self, index: usize) -> Option<u32> {
+        self.entries[index + 1..]
+            .iter()
+            .map(|entry| &entry.record)
+            .take_while(|record| matches!(record, Record::Teams { .. } | Record::Move { .. }))
+            .find_map(|record| match record {
+                Record::Move { timed_out, .. } => *timed_out,
+                _ => None,
+            })
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file =
+            File::open(path).with_context(|| format!("could not open {}", path.display()))?;
+
+        let mut entries = Vec::new();
+
+        for (index, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+
+            if line.is_empty() {
+                continue;
+            }
+
+            entries.push(
+                serde_json::from_str::<Entry>(&line)
+                    .with_context(|| format!("{}:{}", path.display(), index + 1))?,
+            );
+        }
+
+        let Some(Entry {
+            record: Record::Header {
+                version, bot, room, ..
+            },
+            ..
+        }) = entries.first()
+        else {
+            bail!("{} does not start with a header", path.display());
+        };
+
+        ensure!(
+            *version <= VERSION,
+            "{} has version {}, but only up to {} is supported",
+            path.display(),
+            version,
+            VERSION
+        );
+
+        Ok(Self {
+            version: *version,
+            bot: bot.clone(),
+            room: room.clone(),
+            entries,
+        })
+    }
+
+    /// Feeds the recorded events into `bot`, calling `on_turn` after every
+    /// patch with the move that was recorded and the move `bot` makes now.
+    pub fn rerun(&self, bot: &mut Bot, mut on_turn: impl FnMut(&Bot, &Turn)) {
+        let mut teams = UpdateTeams::new();
+        let mut pending: Option<Turn> = None;
+        let mut index = 0;
+
+        for (position, entry) in self.entries.iter().enumerate() {
+            match &entry.record {
+                Record::Header { game, .. } => bot.reseed(*game),
+                Record::GameStart(game_start) => {
+                    bot.start(game_start.clone());
+                }
+                Record::Teams { teams: new_teams } => {
+                    teams = new_teams.clone();
+                }
+                Record::Patch(patch) => {
+                    if let Some(turn) = pending.take() {
+                        on_turn(bot, &turn);
+                    }
+
+                    bot.patch(patch);
+                    let replayed = if self.version >= 5 {
+                        bot.next_move_replayed(self.timed_out(position))
+                    } else {
+                        bot.next_move()
+                    };
+                    bot.update_teammates(&patch.rank, &teams);
+
+                    pending = Some(Turn {
+                        index,
+                        recorded: None,
+                        replayed,
+                    });
+                    index += 1;
+                }
+                Record::Stale(patch) => {
+                    if let Some(turn) = pending.take() {
+                        on_turn(bot, &turn);
+                    }
+
+                    bot.patch(patch);
+                    bot.update_teammates(&patch.rank, &teams);
+
+                    pending = Some(Turn {
+                        index,
+                        recorded: None,
+                        replayed: None,
+                    });
+                    index += 1;
+                }
+                Record::Move { movement, .. } => {
+                    if let Some(turn) = pending.as_mut() {
+                        turn.recorded = *movement;
+                    }
+                }
+                Record::Win { .. } => {
+                    if let Some(turn) = pending.take() {
+                        on_turn(bot, &turn);
+                    }
+
+                    bot.reset();
+                    bot.teammates.clear();
+                }
+            }
+        }
+
+        if let Some(turn) = pending.take() {
+            on_turn(bot, &turn);
+        }
+    }
+}
@@ -0,0 +1,424 @@
+//! An offline engine for the polygen rules modelled in `map.rs`, so that
+//! several [`Bot`]s can play whole games against each other in-process.
+//!
+//! The rules follow what the bot already assumes about the server:
+//!
+//! - players see their own tiles and everything adjacent to them; hidden
+//!   mountains and cities show up as `UnknownMountain` and `UnknownCity`,
+//!   everything else as `Unknown`;
+//! - every turn generals and owned cities grow by one army, and every
+//!   [`LAND_GROWTH_INTERVAL`] turns every owned tile grows by one as well;
+//! - a move takes all but one army from its tile, or half of them when the
+//!   `Movement` flag is set, and captures the destination if it arrives with
+//!   more armies than the destination holds;
+//! - capturing a general eliminates its owner, turning the general into a
+//!   city and handing every remaining tile over with half its armies.
+//!
+//! Everything is driven by a single seed, so a game between the same bots
+//! with the same seed is replayed move for move. The seed doubles as the
+//! game id for bots that configure a `seed` of their own.
+
+use crate::{
+    bot::{Bot, Movement},
+    event::{GameStart, Patch, Rank, Updates},
+    map::{Land, LandType, Map, MaybeLand, Pos},
+    BotConfig, BotData,
+};
+use anyhow::{ensure, Result};
+use fastrand::Rng;
+use serde::Deserialize;
+
+/// Owned land grows by one army every this many turns.
+pub const LAND_GROWTH_INTERVAL: u32 = 25;
+
+/// Leaks `bot` as the `&'static` config a simulated [`Bot`] plays with,
+/// without a room, a server or replays.
+pub fn bot_data(bot: BotConfig<'static>) -> &'static BotData {
+    Box::leak(Box::new(BotData {
+        bot,
+        room: None,
+        base_url: "",
+        replay_dir: None,
+    }))
+}
+
+/// Parses one `[[bots]]` entry of a config file and leaks it like
+/// [`bot_data`]. Panics if `toml` is not a valid bot config, as it is meant
+/// for configs written into tests and benchmarks.
+pub fn leak_bot_data(toml: &str) -> &'static BotData {
+    let toml: &'static str = Box::leak(toml.to_string().into_boxed_str());
+    bot_data(toml::from_str(toml).expect("invalid bot config"))
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct Options {
+    pub mode: String,
+    pub width: usize,
+    pub height: usize,
+    pub mountain_density: f64,
+    pub city_density: f64,
+    pub max_turns: u32,
+}
+
+impl Default for Options {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            mode: "六边形".to_string(),
+            width: 20,
+            height: 20,
+            mountain_density: 0.2,
+            city_density: 0.04,
+            max_turns: 2000,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Outcome {
+    /// Index of the winning bot, or `None` if `max_turns` ran out first.
+    pub winner: Option<usize>,
+    pub turns: u32,
+}
+
+struct Player {
+    bot: Bot,
+    view: Map,
+    alive: bool,
+}
+
+pub struct Game {
+    gm: Map,
+    players: Vec<Player>,
+    turn: u32,
+    max_turns: u32,
+}
+
+fn generate(options: &Options, players: usize, rng: &mut Rng) -> Option<Map> {
+    let mut gm = Map::with_size(options.width, options.height, options.mode.clone());
+
+    let mut tiles: Vec<Pos> = gm.iter().into_iter().map(|(pos, _)| pos).collect();
+    rng.shuffle(&mut tiles);
+
+    let mountains = (tiles.len() as f64 * options.mountain_density) as usize;
+    let cities = (tiles.len() as f64 * options.city_density) as usize;
+
+    for &pos in tiles.iter().take(mountains) {
+        gm[pos].r#type = LandType::Mountain;
+    }
+
+    for &pos in tiles.iter().skip(mountains).take(cities) {
+        gm[pos].r#type = LandType::City;
+        gm[pos].amount = rng.i32(40..=50);
+    }
+
+    let min_dist = ((options.width + options.height) / (players + 1)) as u32;
+
+    let mut generals = Vec::new();
+    let mut reachable = Vec::new();
+
+    for &pos in &tiles[mountains + cities..] {
+        if generals.len() == players {
+            break;
+        }
+
+        if reachable
+            .iter()
+            .all(|dist: &Vec<Option<u32>>| matches!(dist[gm.id(pos)], Some(d) if d >= min_dist))
+        {
+            generals.push(pos);
+            reachable.push(gm.distances([pos], u32::MAX));
+        }
+    }
+
+    if generals.len() < players {
+        return None;
+    }
+
+    for (color, &pos) in generals.iter().enumerate() {
+        gm[pos] = Land {
+            color: color as u8 + 1,
+            r#type: LandType::General,
+            amount: 1,
+        };
+    }
+
+    Some(gm)
+}
+
+impl Game {
+    /// Sets up a game between `bots`, who play as colors `1..=bots.len()`.
+    pub fn new(bots: &[&'static BotData], options: &Options, seed: u64) -> Result<Self> {
+        ensure!(bots.len() >= 2, "a game needs at least two bots");
+        ensure!(bots.len() < u8::MAX as usize, "too many bots");
+
+        let mut rng = Rng::with_seed(seed);
+
+        let mut attempts = 0;
+        let gm = loop {
+            ensure!(
+                attempts < 100,
+                "could not generate a map for {} bots",
+                bots.len()
+            );
+            attempts += 1;
+
+            if let Some(gm) = generate(options, bots.len(), &mut rng) {
+                break gm;
+            }
+        };
+
+        let mut players = Vec::new();
+
+        for (index, &config) in bots.iter().enumerate() {
+            let mut bot = Bot::new(config)?;
+            bot.seed(rng.u64(..));
+            // Bots with a `seed` of their own play every game the same way.
+            bot.reseed(seed);
+
+            players.push(Player {
+                bot,
+                view: Map::new(),
+                alive: true,
+            });
+
+            let color = index as u8 + 1;
+            let view = Self::view(&gm, color);
+
+            players[index].bot.start(GameStart {
+                maybe_map: view.maybe_map(),
+                my_color: color as i32,
+            });
+            players[index].view = view;
+        }
+
+        Ok(Self {
+            gm,
+            players,
+            turn: 0,
+            max_turns: options.max_turns,
+        })
+    }
+
+    /// The full, fog-free state of the game.
+    #[inline]
+    pub const fn map(&self) -> &Map {
+        &self.gm
+    }
+
+    #[inline]
+    pub const fn turn(&self) -> u32 {
+        self.turn
+    }
+
+    #[inline]
+    pub fn bot(&self, index: usize) -> &Bot {
+        &self.players[index].bot
+    }
+
+    /// What `color` is allowed to see of `gm`.
+    pub fn view(gm: &Map, color: u8) -> Map {
+        let mut view = gm.clone();
+
+        for (pos, land) in gm.iter() {
+            let visible = land.color == color
+                || gm
+                    .adjacent(pos)
+                    .into_iter()
+                    .any(|pos| gm[pos].color == color);
+
+            if !visible {
+                view[pos] = Land {
+                    color: 0,
+                    r#type: match land.r#type {
+                        LandType::Mountain => LandType::UnknownMountain,
+                        LandType::City => LandType::UnknownCity,
+                        _ => LandType::Unknown,
+                    },
+                    amount: 0,
+                };
+            }
+        }
+
+        view
+    }
+
+    fn apply(&mut self, color: u8, (from, to, half): Movement) {
+        let gm = &mut self.gm;
+
+        if !gm.check(from)
+            || !gm.check(to)
+            || gm[from].color != color
+            || gm[from].amount <= 1
+            || !gm.neighbours(from).contains(&to)
+        {
+            return;
+        }
+
+        let moved = if half {
+            gm[from].amount / 2
+        } else {
+            gm[from].amount - 1
+        };
+
+        gm[from].amount -= moved;
+
+        if gm[to].color == color {
+            gm[to].amount += moved;
+            return;
+        }
+
+        if moved <= gm[to].amount {
+            gm[to].amount -= moved;
+            return;
+        }
+
+        let loser = gm[to].color;
+
+        gm[to].amount = moved - gm[to].amount;
+        gm[to].color = color;
+
+        if gm[to].r#type == LandType::General {
+            gm[to].r#type = LandType::City;
+
+            for (_, land) in gm.iter_mut() {
+                if land.color == loser {
+                    land.color = color;
+                    land.amount = (land.amount + 1) / 2;
+                }
+            }
+
+            self.players[loser as usize - 1].alive = false;
+        }
+    }
+
+    fn grow(&mut self) {
+        for (_, land) in self.gm.iter_mut() {
+            if land.color == 0 {
+                continue;
+            }
+
+            match land.r#type {
+                LandType::General | LandType::City => land.amount += 1,
+                LandType::Land if self.turn.is_multiple_of(LAND_GROWTH_INTERVAL) => {
+                    land.amount += 1
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// The scoreboard sent with every patch: army and land per living player.
+    pub fn rank(&self) -> Rank {
+        let mut rank: Rank = self
+            .players
+            .iter()
+            .enumerate()
+            .filter(|(_, player)| player.alive)
+            .map(|(index, player)| {
+                let name = player.bot.config().bot.name.to_string();
+                (None, index as i32 + 1, name, 0, 0)
+            })
+            .collect();
+
+        for (_, land) in self.gm.iter() {
+            if let Some(entry) = rank.iter_mut().find(|entry| entry.1 == land.color as i32) {
+                entry.3 += land.amount.max(0) as u32;
+                entry.4 += 1;
+            }
+        }
+
+        rank
+    }
+
+    /// Plays one turn: every living bot moves, armies grow and each bot is
+    /// sent the changes it can see. Returns the outcome once the game is over.
+    pub fn step(&mut self) -> Option<Outcome> {
+        let mut moves = Vec::new();
+
+        for (index, player) in self.players.iter_mut().enumerate() {
+            if player.alive {
+                if let Some(movement) = player.bot.next_move() {
+                    moves.push((index as u8 + 1, movement));
+                }
+            }
+        }
+
+        // Rotate who moves first so that no color is favoured in races.
+        if !moves.is_empty() {
+            let shift = self.turn as usize % moves.len();
+            moves.rotate_left(shift);
+        }
+
+        for (color, movement) in moves {
+            if self.players[color as usize - 1].alive {
+                self.apply(color, movement);
+            }
+        }
+
+        self.turn += 1;
+        self.grow();
+
+        let rank = self.rank();
+
+        for index in 0..self.players.len() {
+            let view = Self::view(&self.gm, index as u8 + 1);
+            let player = &mut self.players[index];
+
+            let mut updates = Updates::new();
+
+            for (pos, land) in view.iter() {
+                let old = &player.view[pos];
+
+                if old.color != land.color || old.r#type != land.r#type || old.amount != land.amount
+                {
+                    updates.push((
+                        view.id(pos),
+                        MaybeLand {
+                            c: (old.color != land.color).then_some(land.color),
+                            t: (old.r#type != land.r#type).then_some(land.r#type as u8),
+                            a: (old.amount != land.amount).then_some(land.amount - old.amount),
+                        },
+                    ));
+                }
+            }
+
+            if player.alive {
+                player.bot.patch(&Patch {
+                    updates,
+                    rank: rank.clone(),
+                });
+            }
+
+            player.view = view;
+        }
+
+        let alive: Vec<usize> = (0..self.players.len())
+            .filter(|&index| self.players[index].alive)
+            .collect();
+
+        if alive.len() == 1 {
+            Some(Outcome {
+                winner: Some(alive[0]),
+                turns: self.turn,
+            })
+        } else if self.turn >= self.max_turns {
+            Some(Outcome {
+                winner: None,
+                turns: self.turn,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Plays until somebody wins or `max_turns` is reached.
+    pub fn run(&mut self) -> Outcome {
+        loop {
+            if let Some(outcome) = self.step() {
+                return outcome;
+            }
+        }
+    }
+}
@@ -1,14 +1,22 @@
 use crate::{
     bot::Bot,
     event::{self, callback, UpdateTeams},
-    map::Map,
-    AutoReady, BotData,
+    worker::{self, Event},
+    AutoReady, BotData, ReconnectConfig,
 };
-use anyhow::Result;
+use anyhow::{anyhow, Context, Result};
+use fastrand::Rng;
 use parking_lot::Mutex;
-use rust_socketio::{client::Client, ClientBuilder, RawClient};
-use serde_json::json;
-use std::sync::Arc;
+use rust_socketio::{client::Client, ClientBuilder, Payload, RawClient};
+use serde_json::{json, Value};
+use std::{
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
 
 fn ready(socket: &RawClient, config: &BotData) -> Result<()> {
     if let AutoReady::Unconditional(true) = config.bot.auto_ready {
@@ -18,106 +26,430 @@ fn ready(socket: &RawClient, config: &BotData) -> Result<()> {
     Ok(())
 }
 
-fn vote(socket: &RawClient, config: &BotData) -> Result<()> {
+fn votes(config: &BotData) -> Vec<Value> {
+    let mut votes = Vec::new();
+
     if let Some(room) = config.room {
         if let Some(mode) = room.mode {
-            socket.emit("vote", json!({"item": "mode", "value": mode}))?;
+            votes.push(json!({"item": "mode", "value": mode}));
         }
         if let Some(map) = room.map {
-            socket.emit("vote", json!({"item": "map", "value": map}))?;
+            votes.push(json!({"item": "map", "value": map}));
         }
         if let Some(speed) = room.speed {
-            socket.emit("vote", json!({"item": "speed", "value": speed}))?;
+            votes.push(json!({"item": "speed", "value": speed}));
         }
     }
 
+    votes
+}
+
+fn vote(socket: &RawClient, config: &BotData) -> Result<()> {
+    for vote in votes(config) {
+        socket.emit("vote", vote)?;
+    }
+
     Ok(())
 }
 
-pub fn new_bot(config: &'static BotData) -> Result<Client> {
-    let global_bot = Arc::new(Mutex::new(Bot::new(config)));
+/// Hands `event` over to the bot's worker thread.
+fn send(worker: &Sender<Event>, config: &BotData, event: Event) -> Result<()> {
+    worker
+        .send(event)
+        .map_err(|_| anyhow!("{}'s worker thread has stopped", config.bot.name))
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Status {
+    Connecting,
+    Connected,
+    Reconnecting,
+    Failed,
+    Stopped,
+}
+
+/// The config the bot runs with. A reloaded config waits in `pending` until
+/// the game in progress ends.
+struct Settings {
+    current: &'static BotData,
+    pending: Option<&'static BotData>,
+    in_game: bool,
+}
+
+impl Settings {
+    /// Switches to `config`, returning whether the room votes changed.
+    fn apply(&mut self, worker: &Sender<Event>, config: &'static BotData) -> Result<bool> {
+        let revote = self.current.room != config.room;
+
+        self.current = config;
+        send(worker, config, Event::Reload(config))?;
+
+        Ok(revote)
+    }
+}
+
+struct Connection {
+    status: Status,
+    /// Incremented on every successful connection.
+    generation: u64,
+    /// Whether the client has connected but the server has not confirmed
+    /// it with `open` yet. Losing it then counts as losing the connection.
+    opening: bool,
+}
+
+/// Marks the connection as lost and wakes up [`reconnect`], unless it was
+/// already lost or closed on purpose.
+fn lose(connection: &Mutex<Connection>, config: &BotData, lost: &Sender<()>) {
+    let mut connection = connection.lock();
+
+    let opening = connection.opening && connection.status != Status::Stopped;
+
+    if connection.status != Status::Connected && !opening {
+        return;
+    }
+
+    connection.opening = false;
+
+    error!("{} disconnected", config.bot.name);
+
+    if config.bot.reconnect.attempts == 0 {
+        connection.status = Status::Failed;
+        return;
+    }
+
+    connection.status = Status::Reconnecting;
+    let _ = lost.send(());
+}
+
+/// How long to wait before reconnection attempt `attempt`, counted from 0;
+/// see [`ReconnectConfig`].
+fn backoff(reconnect: &ReconnectConfig, attempt: u8, rng: &mut Rng) -> Duration {
+    let delay = (reconnect.min_delay_ms as f64 * 1.5f64.powi(attempt as i32))
+        .min(reconnect.max_delay_ms as f64);
+
+    Duration::from_millis((delay * (1.0 + rng.f64() / 2.0)) as u64)
+}
+
+/// Connects with a freshly resolved cookie, so that a cookie that changed
+/// since the last connection is picked up. If the server rejects it, the
+/// session may have expired; logs in again if the cookie allows it.
+fn connect(builder: &ClientBuilder, config: &BotData) -> Result<Client> {
+    let cookie = config
+        .bot
+        .cookie
+        .resolve(config.base_url)
+        .with_context(|| format!("failed to get the cookie of {}", config.bot.name))?;
+
+    match builder.clone().opening_header("cookie", cookie).connect() {
+        Ok(client) => Ok(client),
+        Err(err) => match config.bot.cookie.refresh(config.base_url) {
+            Some(cookie) => {
+                warn!(
+                    "{} could not connect ({}), logging in again",
+                    config.bot.name, err
+                );
+
+                Ok(builder
+                    .clone()
+                    .opening_header("cookie", cookie?)
+                    .connect()?)
+            }
+            None => Err(err.into()),
+        },
+    }
+}
+
+/// Replaces the client with a new connection every time [`lose`] reports
+/// the old one lost. Gives up and reports the bot as failed once
+/// `attempts` attempts in a row have failed, and stops with the bot. An
+/// attempt whose connection is lost before `open` counts as failed.
+fn reconnect(
+    builder: ClientBuilder,
+    client: Arc<Mutex<Client>>,
+    connection: Arc<Mutex<Connection>>,
+    settings: Arc<Mutex<Settings>>,
+    lost: Receiver<()>,
+) {
+    let mut rng = Rng::new();
+    let mut attempt = 0;
+    // The generation before the last connection we made, which is still
+    // current if that connection was lost before `open`.
+    let mut unopened = None;
+
+    while lost.recv().is_ok() {
+        if connection.lock().status == Status::Stopped {
+            return;
+        }
+
+        if unopened != Some(connection.lock().generation) {
+            attempt = 0;
+        }
+
+        // Whatever is left of the old connection would only get in the way.
+        // Not under the lock, as this calls `close`.
+        let old = client.lock().clone();
+        let _ = old.disconnect();
+
+        loop {
+            let config = settings.lock().current;
+            let reconnect = config.bot.reconnect;
+
+            if attempt == reconnect.attempts {
+                let mut connection = connection.lock();
+
+                if connection.status == Status::Reconnecting {
+                    connection.status = Status::Failed;
+                    error!(
+                        "{} failed to reconnect after {} attempts",
+                        config.bot.name, reconnect.attempts
+                    );
+                }
+
+                return;
+            }
+
+            thread::sleep(backoff(&reconnect, attempt, &mut rng));
+            attempt += 1;
+
+            if connection.lock().status == Status::Stopped {
+                return;
+            }
+
+            let generation = connection.lock().generation;
+
+            match connect(&builder, config) {
+                Ok(new) => {
+                    *client.lock() = new.clone();
+
+                    {
+                        let mut connection = connection.lock();
+
+                        // `open` may have come already.
+                        if connection.generation == generation {
+                            connection.opening = true;
+                        }
+
+                        // `disconnect` may have closed the old client meanwhile.
+                        if connection.status != Status::Stopped {
+                            unopened = Some(generation);
+                            break;
+                        }
+                    }
+
+                    let _ = new.disconnect();
+                    return;
+                }
+                Err(err) => warn!(
+                    "{} could not reconnect (attempt {} of {}): {:?}",
+                    config.bot.name, attempt, reconnect.attempts, err
+                ),
+            }
+        }
+    }
+}
+
+/// A running bot. Dropping it does not disconnect the bot.
+pub struct BotHandle {
+    client: Arc<Mutex<Client>>,
+    connection: Arc<Mutex<Connection>>,
+    settings: Arc<Mutex<Settings>>,
+    worker: Sender<Event>,
+    /// Wakes up [`reconnect`] so that it sees the bot has stopped.
+    lost: Sender<()>,
+}
+
+impl BotHandle {
+    /// The config the bot currently runs with.
+    #[inline]
+    pub fn config(&self) -> &'static BotData {
+        self.settings.lock().current
+    }
+
+    /// Makes the bot run with `config` from the next game on, or right away
+    /// if it is not playing. Only settings that can change without
+    /// reconnecting are picked up; see [`needs_restart`].
+    pub fn reload(&self, config: &'static BotData) -> Result<()> {
+        let mut settings = self.settings.lock();
+
+        if settings.in_game {
+            settings.pending = Some(config);
+            return Ok(());
+        }
+
+        if settings.apply(&self.worker, config)? {
+            let client = self.client.lock();
+
+            for vote in votes(config) {
+                client.emit("vote", vote)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    pub fn status(&self) -> Status {
+        self.connection.lock().status
+    }
+
+    pub fn disconnect(&self) -> Result<()> {
+        self.connection.lock().status = Status::Stopped;
+        let _ = self.lost.send(());
+
+        let client = self.client.lock().clone();
+        client.disconnect()?;
+        Ok(())
+    }
+}
+
+/// Whether switching a running bot from `old` to `new` takes a new
+/// connection or a new `Bot`, rather than [`BotHandle::reload`].
+pub fn needs_restart(old: &BotData, new: &BotData) -> bool {
+    old.base_url != new.base_url
+        || old.bot.cookie != new.bot.cookie
+        || old.bot.reconnect != new.bot.reconnect
+        || old.bot.strategy != new.bot.strategy
+        || old.bot.opening != new.bot.opening
+}
+
+pub fn new_bot(config: &'static BotData) -> Result<BotHandle> {
+    let global_worker = worker::spawn(Bot::new(config)?)?;
+    let global_settings = Arc::new(Mutex::new(Settings {
+        current: config,
+        pending: None,
+        in_game: false,
+    }));
     let global_is_ready = Arc::new(Mutex::new(false));
     let global_teams = Arc::new(Mutex::new(UpdateTeams::new()));
+    let global_connection = Arc::new(Mutex::new(Connection {
+        status: Status::Connecting,
+        generation: 0,
+        opening: true,
+    }));
+    let (global_lost, lost) = channel();
 
+    let is_ready = global_is_ready.clone();
+    let connection = global_connection.clone();
+    let settings = global_settings.clone();
     let open = move |_, socket: RawClient| {
-        info!("{} connected", config.bot.name);
+        let config = settings.lock().current;
+
+        {
+            let mut connection = connection.lock();
+
+            if connection.status == Status::Stopped {
+                return Ok(());
+            }
+
+            if connection.generation == 0 {
+                info!("{} connected", config.bot.name);
+            } else {
+                info!("{} reconnected", config.bot.name);
+            }
+
+            connection.status = Status::Connected;
+            connection.generation += 1;
+            connection.opening = false;
+        }
+
+        // A fresh connection is never ready, whatever it was before.
+        *is_ready.lock() = false;
+
         socket.emit("joinRoom", json!(config.bot.room))?;
         vote(&socket, config)?;
         ready(&socket, config)
     };
 
-    let bot = global_bot.clone();
+    let connection = global_connection.clone();
+    let settings = global_settings.clone();
+    let sender = global_lost.clone();
+    let close = move |_, _| lose(&connection, settings.lock().current, &sender);
+
+    // Errors from polling mean the connection broke without a word from the
+    // server, and no `close` follows them.
+    let connection = global_connection.clone();
+    let settings = global_settings.clone();
+    let sender = global_lost.clone();
+    let error = move |payload, _| {
+        let config = settings.lock().current;
+
+        if let Payload::String(err) = payload {
+            warn!("{}: {}", config.bot.name, err);
+        }
+
+        lose(&connection, config, &sender);
+    };
+
+    let worker = global_worker.clone();
+    let settings = global_settings.clone();
     let game_start = move |payload: String, _| {
         use event::GameStart;
 
         let game_start: GameStart = serde_json::from_str(&payload)?;
 
-        let mut bot = bot.lock();
-        bot.target = None;
-        bot.gm = Map::from(game_start.maybe_map);
-        bot.my_color = if game_start.my_color == -1 {
-            0
-        } else {
-            game_start.my_color as u8
+        let config = {
+            let mut settings = settings.lock();
+            settings.in_game = true;
+            settings.current
         };
 
-        Ok(())
+        send(&worker, config, Event::GameStart(game_start))
     };
 
-    let bot = global_bot.clone();
-    let teams = global_teams.clone();
+    let worker = global_worker.clone();
+    let settings = global_settings.clone();
     let patch = move |payload: String, socket: RawClient| {
         use event::Patch;
 
+        let received = Instant::now();
+        let config = settings.lock().current;
+
         let data =
             lz_str::decompress_from_utf16(&serde_json::from_str::<String>(&payload)?).unwrap();
         let string = String::from_utf16(data.as_slice())?;
         let patch: Patch = serde_json::from_str(&string)?;
 
-        let mut bot = bot.lock();
-
-        for (id, data) in patch.updates {
-            let y = (id - 1) % bot.gm.width + 1;
-            let x = (id - y) / bot.gm.width + 1;
-            let pos = (x, y);
-            bot.gm[pos].patch(data);
-        }
-
-        if let Some(movement) = bot.expand() {
-            socket.emit("move", json!(movement))?;
-        }
-
-        bot.teammates.clear();
-        let teams = teams.lock();
-        let bot_name = config.bot.name.to_string();
-
-        for (_, color, username, _, _) in patch.rank {
-            if color != -1
-                && (color as u8) != bot.my_color
-                && username != bot_name
-                && teams
-                    .iter()
-                    .any(|(_, players)| players.contains(&bot_name) && players.contains(&username))
-            {
-                bot.teammates.push(color as u8);
-            }
-        }
-
-        Ok(())
+        send(
+            &worker,
+            config,
+            Event::Patch {
+                patch,
+                received,
+                socket,
+            },
+        )
     };
 
-    let bot = global_bot;
+    let worker = global_worker.clone();
     let is_ready = global_is_ready.clone();
     let teams = global_teams.clone();
+    let settings = global_settings.clone();
     let win = move |payload: String, socket| {
         let winner: &str = serde_json::from_str(&payload)?;
 
-        info!("Room {}: {} won", config.bot.room, winner);
+        let mut guard = settings.lock();
+        guard.in_game = false;
 
-        let mut bot = bot.lock();
+        info!("Room {}: {} won", guard.current.bot.room, winner);
 
-        bot.target = None;
-        bot.teammates.clear();
+        send(
+            &worker,
+            guard.current,
+            Event::Win {
+                winner: winner.to_string(),
+            },
+        )?;
+
+        if let Some(config) = guard.pending.take() {
+            info!("{} reloaded its config", config.bot.name);
+
+            if guard.apply(&worker, config)? {
+                vote(&socket, config)?;
+            }
+        }
+
+        let config = guard.current;
+        drop(guard);
 
         *is_ready.lock() = false;
 
@@ -147,11 +479,16 @@ pub fn new_bot(config: &'static BotData) -> Result<Client> {
 
     let is_ready = global_is_ready;
     let teams = global_teams;
+    let worker = global_worker.clone();
+    let settings = global_settings.clone();
     let update_teams = move |payload: String, socket: RawClient| {
+        let config = settings.lock().current;
         let mut teams = teams.lock();
 
         *teams = serde_json::from_str::<UpdateTeams>(&payload)?;
 
+        send(&worker, config, Event::Teams(teams.clone()))?;
+
         if let AutoReady::Conditional { more_than } = config.bot.auto_ready {
             let count = teams
                 .iter()
@@ -173,17 +510,31 @@ pub fn new_bot(config: &'static BotData) -> Result<Client> {
         Ok(())
     };
 
-    let client = ClientBuilder::new(config.base_url)
-        .opening_header("cookie", config.bot.cookie)
+    // Reconnecting is left to `reconnect`, which knows when to give up.
+    let builder = ClientBuilder::new(config.base_url)
+        .reconnect(false)
         .on("open", callback(open))
-        .on("close", move |_, _| {
-            error!("{} disconnected", config.bot.name)
-        })
+        .on("close", close)
+        .on("error", error)
         .on("gameStart", callback(game_start))
         .on("patch", callback(patch))
         .on("win", callback(win))
-        .on("updateTeams", callback(update_teams))
-        .connect()?;
-
-    Ok(client)
+        .on("updateTeams", callback(update_teams));
+
+    let client = Arc::new(Mutex::new(connect(&builder, config)?));
+
+    let reconnecting = client.clone();
+    let connection = global_connection.clone();
+    let settings = global_settings.clone();
+    thread::Builder::new()
+        .name(format!("{}-reconnect", config.bot.name))
+        .spawn(move || reconnect(builder, reconnecting, connection, settings, lost))?;
+
+    Ok(BotHandle {
+        client,
+        connection: global_connection,
+        settings: global_settings,
+        worker: global_worker,
+        lost: global_lost,
+    })
 }
@@ -0,0 +1,99 @@
+use crate::{
+    bot::Movement,
+    infer::Inference,
+    map::{Map, Pos},
+    BotData,
+};
+use fastrand::Rng;
+use std::time::Instant;
+
+pub mod defense;
+pub mod gather;
+mod greedy;
+
+pub use greedy::GreedyExpandStrategy;
+
+/// Everything a strategy may observe when choosing a move.
+pub struct Context<'a> {
+    pub gm: &'a Map,
+    pub my_color: u8,
+    pub teammates: &'a [u8],
+    /// The current turn, see [`crate::bot::Clock`].
+    pub turn: u32,
+    pub inference: &'a Inference,
+    pub config: &'static BotData,
+    pub rng: &'a mut Rng,
+    /// When to stop refining the move and play the best one found so far.
+    pub budget: Budget,
+    /// Calls to [`Context::out_of_time`] so far.
+    pub checks: u32,
+    /// The first call to [`Context::out_of_time`] that returned `true`,
+    /// counted from 0.
+    pub timed_out: Option<u32>,
+}
+
+/// How much a strategy may search before settling on a move.
+#[derive(Clone, Copy, Debug)]
+pub enum Budget {
+    /// A fixed amount, see `BotConfig::calc_cnt`.
+    Rounds,
+    /// Until this instant.
+    Until(Instant),
+    /// As long as a move chosen with a deadline did: out of time from the
+    /// recorded `Context::timed_out` on, or never if it is `None`.
+    Replay(Option<u32>),
+}
+
+impl Context<'_> {
+    /// Whether the deadline has passed. Replays give the same answers as
+    /// when the move was chosen, which the wall clock would not.
+    #[inline]
+    pub fn out_of_time(&mut self) -> bool {
+        let check = self.checks;
+        self.checks += 1;
+
+        let out = match self.budget {
+            Budget::Rounds => false,
+            Budget::Until(deadline) => Instant::now() >= deadline,
+            Budget::Replay(timed_out) => timed_out.is_some_and(|timed_out| check >= timed_out),
+        };
+
+        if out && self.timed_out.is_none() {
+            self.timed_out = Some(check);
+        }
+
+        out
+    }
+}
+
+pub trait Strategy: Send {
+    /// Chooses the next movement, or `None` to skip this turn.
+    fn next_move(&mut self, ctx: &mut Context) -> Option<Movement>;
+
+    /// Forgets any per-game state. Called when a game starts or ends.
+    fn reset(&mut self) {}
+
+    /// The tile the strategy is currently heading for, for debugging.
+    fn target(&self) -> Option<Pos> {
+        None
+    }
+
+    /// The tile holding the army being moved towards `target`, for debugging.
+    fn from(&self) -> Option<Pos> {
+        None
+    }
+
+    /// The tiles the army at `from` is planned to walk through, in order,
+    /// for debugging.
+    fn plan(&self) -> Vec<Pos> {
+        Vec::new()
+    }
+}
+
+/// Looks up a strategy by the name used for `strategy` in `config.toml`.
+pub fn from_name(name: &str) -> Option<Box<dyn Strategy>> {
+    match name {
+        "greedy_expand" => Some(Box::<GreedyExpandStrategy>::default()),
+        _ => None,
+    }
+}
@@ -0,0 +1,153 @@
+//! Keeping our own general alive.
+//!
+//! An enemy tile within `threat_radius` steps of our general is a threat if
+//! it holds more armies than the general will have by the time the enemy
+//! can walk there, counting the armies we could gather back to it with a
+//! turn to spare, one step per turn. The most urgent threat is answered by
+//! capturing it from a neighbouring tile when possible, and otherwise by
+//! pulling an army that can make it back in time one step closer to the
+//! general: the closest one that covers the deficit on its own, or failing
+//! that the largest.
+
+use super::Context;
+use crate::{
+    bot::Movement,
+    map::{LandType, Pos},
+};
+
+pub struct Threat {
+    pub pos: Pos,
+    pub amount: i32,
+    /// Steps the enemy army needs to reach our general.
+    pub distance: u32,
+    /// Armies the enemy would have left over after taking the general as
+    /// it stands when the enemy arrives, reinforcements included.
+    pub deficit: i32,
+}
+
+fn general(ctx: &Context) -> Option<Pos> {
+    ctx.gm.iter().into_iter().find_map(|(pos, land)| {
+        (land.r#type == LandType::General && land.color == ctx.my_color).then_some(pos)
+    })
+}
+
+#[inline]
+fn is_enemy(ctx: &Context, color: u8) -> bool {
+    color != 0 && color != ctx.my_color && !ctx.teammates.contains(&color)
+}
+
+/// Armies that can be brought back to the general in `turns` turns, one
+/// step per turn, taking the largest first. `dist` holds the steps from
+/// the general.
+fn reinforcements(ctx: &Context, home: Pos, dist: &[Option<u32>], turns: u32) -> i32 {
+    let mut armies: Vec<(i32, u32)> = ctx
+        .gm
+        .iter()
+        .into_iter()
+        .filter(|&(pos, land)| pos != home && land.color == ctx.my_color && land.amount > 1)
+        .filter_map(|(pos, land)| {
+            dist[ctx.gm.id(pos)]
+                .filter(|&d| d <= turns)
+                .map(|d| (land.amount - 1, d))
+        })
+        .collect();
+
+    armies.sort_unstable_by_key(|&(amount, _)| -amount);
+
+    let mut left = turns;
+    let mut total = 0;
+
+    for (amount, d) in armies {
+        if d <= left {
+            left -= d;
+            total += amount;
+        }
+    }
+
+    total
+}
+
+/// Every visible enemy army that could take our general, most urgent first.
+pub fn threats(ctx: &Context) -> Vec<Threat> {
+    let radius = ctx.config.bot.threat_radius;
+
+    let Some(home) = general(ctx).filter(|_| radius > 0) else {
+        return Vec::new();
+    };
+
+    let dist = ctx.gm.distances([home], radius);
+    let defenders = ctx.gm[home].amount;
+
+    let mut threats: Vec<Threat> = ctx
+        .gm
+        .iter()
+        .into_iter()
+        .filter(|&(_, land)| is_enemy(ctx, land.color))
+        .filter_map(|(pos, land)| {
+            let distance = dist[ctx.gm.id(pos)]?;
+            // Every step costs the enemy an army while the general grows.
+            let deficit = land.amount - distance as i32 - (defenders + distance as i32);
+
+            if deficit <= 0 {
+                return None;
+            }
+
+            // Keep a turn to spare, so that the threat is called while the
+            // armies can still make it back.
+            let deficit = deficit - reinforcements(ctx, home, &dist, distance.saturating_sub(1));
+
+            (deficit > 0).then_some(Threat {
+                pos,
+                amount: land.amount,
+                distance,
+                deficit,
+            })
+        })
+        .collect();
+
+    threats.sort_unstable_by_key(|threat| (threat.distance, -threat.deficit));
+    threats
+}
+
+/// Answers the most urgent threat, or `None` if the general is safe or
+/// there is nothing useful to do about it.
+pub fn defend(ctx: &Context) -> Option<Movement> {
+    let threat = threats(ctx).into_iter().next()?;
+    let home = general(ctx)?;
+    let gm = ctx.gm;
+
+    // Intercept: take the threatening tile outright if a neighbour can.
+    let interceptor = gm
+        .neighbours(threat.pos)
+        .into_iter()
+        .filter(|&pos| gm[pos].color == ctx.my_color && gm[pos].amount - 1 > threat.amount)
+        .max_by_key(|&pos| gm[pos].amount);
+
+    if let Some(from) = interceptor {
+        return Some((from, threat.pos, false));
+    }
+
+    // Otherwise bring home an army that arrives before the enemy does.
+    let dist = gm.distances([home], threat.distance);
+
+    let (from, d) = gm
+        .iter()
+        .into_iter()
+        .filter(|&(pos, land)| pos != home && land.color == ctx.my_color && land.amount > 1)
+        .filter_map(|(pos, land)| dist[gm.id(pos)].map(|d| (pos, d, land.amount)))
+        .max_by_key(|&(_, d, amount)| {
+            if amount > threat.deficit {
+                (true, -(d as i32), amount)
+            } else {
+                (false, 0, amount)
+            }
+        })
+        .map(|(pos, d, _)| (pos, d))?;
+
+    let to = gm
+        .neighbours(from)
+        .into_iter()
+        .find(|&pos| dist[gm.id(pos)] == Some(d - 1))?;
+
+    Some((from, to, false))
+}
@@ -0,0 +1,72 @@
+//! Collecting scattered armies into one stack.
+//!
+//! The planner grows a BFS spanning tree over our own tiles from a root and
+//! moves every tile's armies to its parent, leaves first, so that each move
+//! carries along everything its subtree has collected so far. Moves that
+//! would carry a single army are not worth a turn and are left out, as are
+//! the subtrees behind them, and the tree is cut at the depth that keeps the
+//! plan within its move budget. The tree never passes through our general
+//! unless it is the root, so the general keeps its armies.
+
+use crate::{
+    bot::Movement,
+    map::{LandType, Map, Pos},
+};
+use std::collections::VecDeque;
+
+/// Plans at most `budget` moves that bring `my_color`'s armies to `root`,
+/// in the order they should be played.
+pub fn plan(gm: &Map, my_color: u8, root: Pos, budget: usize) -> Vec<Movement> {
+    let mut parent = gm.grid(None);
+    let mut depth = gm.grid(0);
+    let mut order = vec![root];
+    let mut q = VecDeque::from([root]);
+
+    parent[gm.id(root)] = Some(root);
+
+    while let Some(cur) = q.pop_front() {
+        for nxt in gm.neighbours(cur) {
+            let id = gm.id(nxt);
+
+            if gm[nxt].color == my_color
+                && gm[nxt].r#type != LandType::General
+                && parent[id].is_none()
+            {
+                parent[id] = Some(cur);
+                depth[id] = depth[gm.id(cur)] + 1;
+                order.push(nxt);
+                q.push_back(nxt);
+            }
+        }
+    }
+
+    let max_depth = order.last().map_or(0, |&pos| depth[gm.id(pos)]);
+
+    for limit in (1..=max_depth).rev() {
+        let mut collected = gm.grid(0);
+        let mut moves = Vec::new();
+
+        // BFS order lists parents before children, so walk it backwards.
+        for &pos in order.iter().skip(1).rev() {
+            let id = gm.id(pos);
+
+            if depth[id] > limit {
+                continue;
+            }
+
+            let total = collected[id] + gm[pos].amount - 1;
+
+            if total > 1 {
+                let to = parent[id].unwrap();
+                collected[gm.id(to)] += total;
+                moves.push((pos, to, false));
+            }
+        }
+
+        if moves.len() <= budget {
+            return moves;
+        }
+    }
+
+    Vec::new()
+}
@@ -0,0 +1,570 @@
+use super::{defense, gather, Budget, Context, Strategy};
+use crate::{
+    bot::Movement,
+    map::{Land, LandType, Pos},
+};
+use fastrand::Rng;
+use std::collections::VecDeque;
+
+/// Search rounds when searching on a deadline; later rounds rarely find
+/// anything the earlier ones missed.
+const MAX_ROUNDS: u32 = 64;
+
+/// The default play style: capture whatever can be taken right now, and
+/// otherwise walk the best army found by BFS towards a scored target.
+/// With `gather_interval` set it also regularly gathers its armies into one
+/// stack next to an enemy general it has seen, if the stack would outnumber
+/// it, and then sends that stack after it.
+#[derive(Default)]
+pub struct GreedyExpandStrategy {
+    target: Option<Pos>,
+    from: Option<Pos>,
+    /// The color whose general `target` is guessed to be, if `target` came
+    /// from the inference rather than from the map.
+    guess: Option<u8>,
+    gathering: VecDeque<Movement>,
+    gather_root: Option<Pos>,
+    /// The rest of the path from `from` to `target` found by the last BFS,
+    /// followed until it stops making sense.
+    plan: VecDeque<Pos>,
+}
+
+impl GreedyExpandStrategy {
+    fn move_to(&self, ctx: &Context, from: Pos, to: Pos) -> Movement {
+        let from_land = &ctx.gm[from];
+        let to_land = &ctx.gm[to];
+
+        let mut half_tag = false;
+
+        if to_land.color != ctx.my_color
+            && to_land.color != 0
+            && (from_land.amount - 1) / 2 > to_land.amount
+            && !ctx.teammates.contains(&to_land.color)
+        {
+            for neighbour in ctx.gm.neighbours(from) {
+                let land = &ctx.gm[neighbour];
+
+                if land.color != ctx.my_color
+                    && land.color != 0
+                    && matches!(land.r#type, LandType::City | LandType::Land)
+                    && neighbour != to
+                    && !ctx.teammates.contains(&land.color)
+                {
+                    half_tag = true;
+                    break;
+                }
+            }
+        }
+
+        if to_land.r#type == LandType::City
+            && to_land.color == 0
+            && from_land.amount > 25
+            && !half_tag
+        {
+            for neighbour in ctx.gm.neighbours(from) {
+                let land = &ctx.gm[neighbour];
+
+                if land.color != ctx.my_color
+                    && !ctx.teammates.contains(&land.color)
+                    && matches!(land.r#type, LandType::City | LandType::Land)
+                    && neighbour != to
+                {
+                    half_tag = true;
+                    break;
+                }
+            }
+        }
+
+        (from, to, half_tag)
+    }
+
+    fn new_target(&mut self, ctx: &mut Context) -> Option<Pos> {
+        let mut targets = Vec::new();
+
+        for (pos, _) in ctx.gm.iter() {
+            let land = ctx.gm.recall(pos);
+
+            if ctx.gm.accessible(pos)
+                && !matches!(land.r#type, LandType::Unknown | LandType::UnknownCity)
+                && land.color != ctx.my_color
+                && !ctx.teammates.contains(&land.color)
+            {
+                targets.push(pos);
+            }
+        }
+
+        ctx.rng.shuffle(&mut targets);
+
+        let get_score = |&pos: &Pos| {
+            let land = ctx.gm.recall(pos);
+            match land.r#type {
+                LandType::General => 1,
+                LandType::City if land.color != 0 => 1,
+                LandType::City => 5,
+                LandType::Land if land.color != 0 => 2,
+                LandType::Land => 3,
+                _ => 9,
+            }
+        };
+
+        targets.sort_unstable_by_key(|target| get_score(target));
+
+        let best = targets.first().copied();
+
+        // Head for a guessed general unless a general or city is in sight.
+        match ctx.inference.most_likely() {
+            Some((color, pos, _)) if best.is_none_or(|best| get_score(&best) > 1) => {
+                self.guess = Some(color);
+                Some(pos)
+            }
+            _ => {
+                self.guess = None;
+                best
+            }
+        }
+    }
+
+    /// Plans a gather into our tile closest to the target, to be played by
+    /// `gather`, if the target is an enemy general the stack can take.
+    fn plan_gather(&mut self, ctx: &mut Context) {
+        if self.target.is_none() {
+            self.target = self.new_target(ctx);
+        }
+
+        let Some(target) = self.target else {
+            return;
+        };
+
+        // Only an enemy general is worth halting the expansion for.
+        let general = ctx.gm.recall(target);
+
+        if general.r#type != LandType::General {
+            return;
+        }
+
+        let dist = ctx.gm.distances([target], u32::MAX);
+
+        let Some((root, _)) = ctx
+            .gm
+            .iter()
+            .into_iter()
+            .filter(|(_, land)| land.color == ctx.my_color)
+            .filter_map(|(pos, land)| dist[ctx.gm.id(pos)].map(|d| (pos, (d, -land.amount))))
+            .min_by_key(|&(_, key)| key)
+        else {
+            return;
+        };
+
+        // Spend at most half of the cycle gathering.
+        let budget = ctx.config.bot.gather_interval as usize / 2;
+
+        let moves = gather::plan(ctx.gm, ctx.my_color, root, budget);
+        let stack = ctx.gm[root].amount
+            + moves
+                .iter()
+                .map(|&(from, _, _)| ctx.gm[from].amount - 1)
+                .sum::<i32>();
+
+        // A stack that cannot take the general would just be thrown away.
+        if stack <= general.amount {
+            return;
+        }
+
+        self.gathering = moves.into();
+        self.gather_root = Some(root);
+    }
+
+    /// Plays the next planned gather move that is still possible. Once the
+    /// last one is played the gathered stack becomes the army `from`.
+    fn gather(&mut self, ctx: &Context) -> Option<Movement> {
+        while let Some(movement @ (from, _, _)) = self.gathering.pop_front() {
+            let land = &ctx.gm[from];
+
+            if land.color != ctx.my_color || land.amount <= 1 {
+                continue;
+            }
+
+            if self.gathering.is_empty() {
+                self.from = self.gather_root;
+                self.plan.clear();
+            }
+
+            return Some(movement);
+        }
+
+        None
+    }
+
+    /// Every capture our tiles can make right now, best first.
+    fn captures(ctx: &mut Context) -> Vec<(Pos, Pos)> {
+        let mut moves = Vec::new();
+
+        for (from, from_land) in ctx.gm.iter() {
+            if from_land.color == ctx.my_color {
+                for to in ctx.gm.neighbours(from) {
+                    let to_land = &ctx.gm[to];
+
+                    let delta = if to_land.r#type == LandType::City && to_land.color != 0 {
+                        2
+                    } else {
+                        1
+                    };
+
+                    if to_land.color != ctx.my_color
+                        && from_land.amount > to_land.amount + delta
+                        && !ctx.teammates.contains(&to_land.color)
+                    {
+                        moves.push((from, to));
+                    }
+                }
+            }
+        }
+
+        ctx.rng.shuffle(&mut moves);
+
+        const fn get_score(from_land: &Land, to_land: &Land) -> u8 {
+            let from_score = match from_land.r#type {
+                LandType::Land => 1,
+                LandType::City => 2,
+                LandType::General => 3,
+                _ => 4,
+            };
+
+            let to_score = match to_land.r#type {
+                LandType::General => 10,
+                LandType::City if to_land.color != 0 => 20,
+                LandType::City => 40,
+                LandType::Land if to_land.color != 0 => 30,
+                LandType::Land => 50,
+                _ => 90,
+            };
+
+            from_score + to_score
+        }
+
+        let gm = ctx.gm;
+
+        moves.sort_unstable_by(|&(from_a, to_a), &(from_b, to_b)| {
+            let score_a = get_score(&gm[from_a], &gm[to_a]);
+            let score_b = get_score(&gm[from_b], &gm[to_b]);
+
+            if score_a != score_b {
+                score_a.cmp(&score_b)
+            } else {
+                (gm[from_b].amount - gm[to_b].amount).cmp(&(gm[from_a].amount - gm[to_a].amount))
+            }
+        });
+
+        moves
+    }
+
+    pub fn expand(&mut self, ctx: &mut Context) -> Option<Movement> {
+        match Self::captures(ctx).first() {
+            Some(&(from, to)) => {
+                if Some(from) == self.from && Some(to) != self.target {
+                    self.target = None;
+                }
+
+                Some(self.move_to(ctx, from, to))
+            }
+            None => self.move_to_target(ctx, 0),
+        }
+    }
+
+    /// Takes the next step of the planned path, or clears the plan if the
+    /// latest patch made it invalid. Forgets `from` once its army is gone.
+    fn follow_plan(&mut self, ctx: &Context) -> Option<Movement> {
+        let gm = ctx.gm;
+        let from = self.from?;
+
+        if gm[from].color != ctx.my_color || gm[from].amount <= 1 {
+            self.from = None;
+            self.plan.clear();
+            return None;
+        }
+
+        let next = *self.plan.front()?;
+
+        let valid = self.plan.back() == self.target.as_ref()
+            && gm.neighbours(from).contains(&next)
+            && self.plan.iter().all(|&pos| {
+                gm.accessible(pos)
+                    && !(gm[pos].r#type == LandType::General
+                        && ctx.teammates.contains(&gm[pos].color))
+            });
+
+        if !valid {
+            self.plan.clear();
+            return None;
+        }
+
+        self.plan.pop_front();
+
+        if self.plan.is_empty() {
+            self.target = None;
+        }
+
+        let ans = self.move_to(ctx, from, next);
+        self.from = Some(next);
+        Some(ans)
+    }
+
+    pub fn move_to_target(&mut self, ctx: &mut Context, try_time: u8) -> Option<Movement> {
+        let calc_cnt = ctx.config.bot.calc_cnt;
+        let score_power = ctx.config.bot.score_power;
+
+        if try_time >= calc_cnt {
+            return None;
+        }
+
+        let ruled_out = |target: Pos, color: u8| {
+            ctx.inference
+                .belief(color)
+                .is_none_or(|belief| belief.probability(target) == 0.0)
+        };
+
+        if self.target.is_none()
+            || matches!(&self.target, Some(target) if ctx.gm.recall(*target).color == ctx.my_color
+                                                   || ctx.teammates.contains(&ctx.gm.recall(*target).color))
+            || matches!((self.target, self.guess), (Some(target), Some(color)) if ruled_out(target, color))
+        {
+            self.target = self.new_target(ctx);
+            self.from = None;
+            self.plan.clear();
+        }
+
+        if let Some(movement) = self.follow_plan(ctx) {
+            return Some(movement);
+        }
+
+        let target = self.target?;
+
+        let gm = ctx.gm;
+        let my_color = ctx.my_color;
+        let teammates = ctx.teammates;
+
+        let get_score = |pos: Pos| {
+            let land = gm.recall(pos);
+
+            if land.color == my_color || teammates.contains(&land.color) {
+                land.amount - 1
+            } else {
+                -land.amount - 1
+            }
+        };
+
+        let mut max_path = Vec::new();
+        let mut max_score = f64::MIN;
+        let mut new_from = None;
+
+        let mut q = VecDeque::new();
+        let mut parent = gm.grid(None::<Pos>);
+
+        let mut found_enemy = ctx.inference.most_likely().is_some();
+
+        for (pos, _) in gm.iter() {
+            let land = gm.recall(pos);
+
+            if land.color != my_color
+                && land.color != 0
+                && !teammates.contains(&land.color)
+                && matches!(
+                    land.r#type,
+                    LandType::General | LandType::City | LandType::Land
+                )
+            {
+                found_enemy = true;
+                break;
+            }
+        }
+
+        let candidates: Vec<Pos> = match self.from {
+            Some(from) => vec![from],
+            None => gm
+                .iter()
+                .into_iter()
+                .filter(|&(pos, land)| {
+                    land.color == my_color
+                        && land.amount > 1
+                        && gm.neighbours(pos).iter().all(|&neighbour| {
+                            let land = &gm[neighbour];
+
+                            land.color == my_color
+                                || land.color == 0
+                                || teammates.contains(&land.color)
+                                || !matches!(land.r#type, LandType::Land | LandType::City)
+                        })
+                })
+                .map(|(pos, _)| pos)
+                .collect(),
+        };
+
+        // One randomised BFS from `from`, returning the score of reaching
+        // the target and the path there.
+        let mut bfs = |from: Pos, rng: &mut Rng| {
+            let mut res = None;
+
+            q.clear();
+            parent.fill(None);
+
+            q.push_back((from, get_score(from), 0));
+            parent[gm.id(from)] = Some(from);
+
+            while let Some((cur, amount, length)) = q.pop_front() {
+                if cur == target {
+                    let score = amount as f64 / (length as f64).powf(score_power);
+
+                    if !(amount < 0 && length < 2) {
+                        let mut path = Vec::new();
+                        let mut pos = cur;
+                        while pos != from {
+                            path.push(pos);
+                            pos = parent[gm.id(pos)].unwrap();
+                        }
+                        path.reverse();
+
+                        res = Some((score, path));
+                    }
+
+                    continue;
+                }
+
+                if !found_enemy && length > 6 {
+                    continue;
+                }
+
+                let mut neighbours = gm.neighbours(cur);
+                rng.shuffle(&mut neighbours);
+
+                for nxt in neighbours {
+                    if gm[nxt].r#type == LandType::General && teammates.contains(&gm[nxt].color)
+                        || parent[gm.id(nxt)].is_some()
+                    {
+                        continue;
+                    }
+
+                    parent[gm.id(nxt)] = Some(cur);
+                    q.push_back((nxt, amount + get_score(nxt), length + 1));
+                }
+            }
+
+            res
+        };
+
+        // Every round tries each candidate once more with a different
+        // shuffle. With `Budget::Rounds` there are `calc_cnt` rounds;
+        // otherwise rounds go on until the budget runs out, checked after
+        // every search so that at least one search is always done.
+        let rounds = match ctx.budget {
+            Budget::Rounds => calc_cnt as u32,
+            Budget::Until(_) | Budget::Replay(_) => MAX_ROUNDS,
+        };
+        let mut best = vec![f64::MIN; candidates.len()];
+
+        'search: for round in 0..rounds {
+            for (index, &from) in candidates.iter().enumerate() {
+                // After three tries, give up on armies far behind the best.
+                if round >= 3 && best[index] < max_score / 2.0 {
+                    continue;
+                }
+
+                if let Some((score, path)) = bfs(from, ctx.rng) {
+                    best[index] = best[index].max(score);
+
+                    if score > max_score {
+                        max_score = score;
+                        max_path = path;
+                        new_from = Some(from);
+                    }
+                }
+
+                if ctx.out_of_time() {
+                    break 'search;
+                }
+            }
+
+            // Whether the target can be reached does not depend on the
+            // shuffle, so if no candidate reached it no round will.
+            if max_path.is_empty() {
+                break;
+            }
+        }
+
+        if max_path.is_empty() {
+            self.target = None;
+
+            if ctx.out_of_time() {
+                return None;
+            }
+
+            return self.move_to_target(ctx, try_time + 1);
+        }
+
+        if self.from.is_none() {
+            self.from = new_from;
+        }
+
+        self.plan = max_path.into();
+        self.follow_plan(ctx)
+    }
+}
+
+impl Strategy for GreedyExpandStrategy {
+    fn next_move(&mut self, ctx: &mut Context) -> Option<Movement> {
+        if let Some(movement) = defense::defend(ctx) {
+            // The army we were walking may be the one called home.
+            self.from = None;
+            self.plan.clear();
+            return Some(movement);
+        }
+
+        let interval = ctx.config.bot.gather_interval;
+
+        if interval > 0 && ctx.turn.is_multiple_of(interval) {
+            self.plan_gather(ctx);
+        }
+
+        // Tiles outside the gather tree keep taking neutral land meanwhile.
+        if !self.gathering.is_empty() {
+            let in_tree = |pos: Pos| {
+                self.gathering
+                    .iter()
+                    .any(|&(from, to, _)| from == pos || to == pos)
+            };
+
+            if let Some(&(from, to)) = Self::captures(ctx)
+                .iter()
+                .find(|&&(from, to)| ctx.gm[to].color == 0 && !in_tree(from))
+            {
+                return Some(self.move_to(ctx, from, to));
+            }
+        }
+
+        if let Some(movement) = self.gather(ctx) {
+            return Some(movement);
+        }
+
+        self.expand(ctx)
+    }
+
+    fn reset(&mut self) {
+        self.target = None;
+        self.from = None;
+        self.guess = None;
+        self.gathering.clear();
+        self.gather_root = None;
+        self.plan.clear();
+    }
+
+    fn target(&self) -> Option<Pos> {
+        self.target
+    }
+
+    fn from(&self) -> Option<Pos> {
+        self.from
+    }
+
+    fn plan(&self) -> Vec<Pos> {
+        self.plan.iter().copied().collect()
+    }
+}
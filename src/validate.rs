@@ -0,0 +1,203 @@
+//! Checks a [`Config`] for mistakes serde cannot catch.
+//!
+//! Errors are settings the bots cannot run with or that the server would
+//! reject; warnings are settings that are probably not what was meant but
+//! still work, such as a map the bot has not heard of.
+
+use crate::{
+    cookie::{self, Cookie},
+    opening::{self, Book},
+    strategy, AutoReady, Config,
+};
+use std::{collections::HashMap, fmt};
+
+/// The modes the server offers.
+pub const MODES: [&str; 2] = ["六边形", "四边形"];
+
+/// The maps the server offers; there may be more, so others only warn.
+pub const MAPS: [&str; 1] = ["随机地图"];
+
+/// The slowest and fastest room speeds the server accepts.
+pub const SPEEDS: (f64, f64) = (0.25, 4.0);
+
+/// The most players a room holds.
+pub const ROOM_CAPACITY: usize = 16;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Clone, Debug)]
+pub struct Problem {
+    pub severity: Severity,
+    /// Where in the config the problem is, e.g. ``bots[1] (`Bot_2`).calc_cnt``.
+    pub location: String,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+
+        write!(f, "{}: {}: {}", severity, self.location, self.message)
+    }
+}
+
+#[derive(Default)]
+struct Problems(Vec<Problem>);
+
+impl Problems {
+    fn push(&mut self, severity: Severity, location: String, message: String) {
+        self.0.push(Problem {
+            severity,
+            location,
+            message,
+        });
+    }
+
+    #[inline]
+    fn error(&mut self, location: String, message: String) {
+        self.push(Severity::Error, location, message);
+    }
+
+    #[inline]
+    fn warning(&mut self, location: String, message: String) {
+        self.push(Severity::Warning, location, message);
+    }
+}
+
+/// Every problem in `config`: the bots' in order, then the rooms' by name.
+pub fn validate(config: &Config) -> Vec<Problem> {
+    let mut problems = Problems::default();
+    let mut names: HashMap<(&str, &str), usize> = HashMap::new();
+
+    for (index, bot) in config.bots.iter().enumerate() {
+        let at = |field: &str| format!("bots[{}] (`{}`).{}", index, bot.name, field);
+
+        if let Some(first) = names.insert((bot.room, bot.name), index) {
+            problems.error(
+                at("name"),
+                format!(
+                    "bots[{}] already joins room `{}` with this name",
+                    first, bot.room
+                ),
+            );
+        }
+
+        match bot.cookie {
+            // Commands are only run when connecting.
+            Cookie::Command { command } if command.trim().is_empty() => {
+                problems.error(at("cookie.command"), "is empty".to_string());
+            }
+            Cookie::Command { .. } => {}
+            // Logging in is left for when the bot starts.
+            Cookie::Login(credentials) => {
+                if credentials.username.trim().is_empty() {
+                    problems.error(at("cookie.username"), "is empty".to_string());
+                }
+
+                if let Err(err) = cookie::interpolate(credentials.password) {
+                    problems.error(at("cookie.password"), format!("{:#}", err));
+                }
+            }
+            cookie => {
+                if let Err(err) = cookie.resolve(config.base_url) {
+                    problems.error(at("cookie"), format!("{:#}", err));
+                }
+            }
+        }
+
+        if !config.rooms.contains_key(&bot.room) {
+            problems.warning(
+                at("room"),
+                format!(
+                    "room `{}` has no entry in [rooms], so nothing is voted for",
+                    bot.room
+                ),
+            );
+        }
+
+        if bot.calc_cnt == 0 {
+            problems.error(at("calc_cnt"), "must be at least 1".to_string());
+        }
+
+        if bot.score_power < 0.0 {
+            problems.error(
+                at("score_power"),
+                format!("is {}, which would prefer longer paths", bot.score_power),
+            );
+        }
+
+        if let AutoReady::Conditional { more_than } = bot.auto_ready {
+            if more_than >= ROOM_CAPACITY {
+                problems.error(
+                    at("auto_ready.more_than"),
+                    format!(
+                        "is {}, but a room holds at most {} players, so the bot never gets ready",
+                        more_than, ROOM_CAPACITY
+                    ),
+                );
+            }
+        }
+
+        if strategy::from_name(bot.strategy).is_none() {
+            problems.error(
+                at("strategy"),
+                format!("unknown strategy `{}`", bot.strategy),
+            );
+        }
+
+        if Book::from_name(bot.opening).is_none() {
+            problems.error(at("opening"), format!("unknown opening `{}`", bot.opening));
+        }
+
+        if bot.opening_turns > opening::MAX_TURNS {
+            problems.error(
+                at("opening_turns"),
+                format!(
+                    "is {}, but planning an opening longer than {} turns holds up the game",
+                    bot.opening_turns,
+                    opening::MAX_TURNS
+                ),
+            );
+        }
+    }
+
+    let mut rooms: Vec<_> = config.rooms.iter().collect();
+    rooms.sort_by_key(|(name, _)| **name);
+
+    for (name, room) in rooms {
+        let at = |field: &str| format!("rooms.`{}`.{}", name, field);
+
+        if let Some(mode) = room.mode {
+            if !MODES.contains(&mode) {
+                problems.error(
+                    at("mode"),
+                    format!("unknown mode `{}`, expected one of {:?}", mode, MODES),
+                );
+            }
+        }
+
+        if let Some(map) = room.map {
+            if !MAPS.contains(&map) {
+                problems.warning(at("map"), format!("unknown map `{}`", map));
+            }
+        }
+
+        if let Some(speed) = room.speed {
+            if !(SPEEDS.0..=SPEEDS.1).contains(&speed) {
+                problems.error(
+                    at("speed"),
+                    format!("is {}, expected {} to {}", speed, SPEEDS.0, SPEEDS.1),
+                );
+            }
+        }
+    }
+
+    problems.0
+}
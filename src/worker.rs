@@ -0,0 +1,197 @@
+//! The thread that plays for a bot.
+//!
+//! Socket callbacks only decode events and send them here, so heartbeats and
+//! other events keep flowing while a move is being searched for. Events are
+//! handled in order, but when several patches are waiting only the newest
+//! one is answered: the older ones are still applied, as patches are diffs,
+//! and recorded as `stale`.
+
+use crate::{
+    bot::Bot,
+    event::{GameStart, Patch, UpdateTeams},
+    latency::Latency,
+    render,
+    replay::{Record, Recorder},
+    BotData,
+};
+use anyhow::Result;
+use rust_socketio::RawClient;
+use serde_json::json;
+use std::{
+    sync::mpsc::{channel, Receiver, Sender},
+    thread,
+    time::Instant,
+};
+
+pub enum Event {
+    GameStart(GameStart),
+    Patch {
+        patch: Patch,
+        /// When the patch arrived, which the time budget counts from.
+        received: Instant,
+        /// Where to send the move.
+        socket: RawClient,
+    },
+    Teams(UpdateTeams),
+    Win {
+        winner: String,
+    },
+    /// Switches to a reloaded config; only sent between games.
+    Reload(&'static BotData),
+}
+
+fn record(recorder: &mut Option<Recorder>, record: impl FnOnce() -> Record) {
+    if let Some(inner) = recorder.as_mut() {
+        if let Err(err) = inner.record(record()) {
+            error!("Failed to write {}: {:?}", inner.path().display(), err);
+            *recorder = None;
+        }
+    }
+}
+
+struct Worker {
+    bot: Bot,
+    config: &'static BotData,
+    teams: UpdateTeams,
+    recorder: Option<Recorder>,
+    latency: Latency,
+    /// Patches applied without choosing a move this game.
+    stale: u32,
+    /// Games started so far; the id of the next game.
+    games: u64,
+}
+
+/// Starts the worker thread for `bot`. It stops once every sender is gone.
+pub fn spawn(bot: Bot) -> Result<Sender<Event>> {
+    let config = bot.config();
+    let (sender, receiver) = channel();
+
+    let worker = Worker {
+        bot,
+        config,
+        teams: UpdateTeams::new(),
+        recorder: None,
+        latency: Latency::default(),
+        stale: 0,
+        games: 0,
+    };
+
+    thread::Builder::new()
+        .name(format!("{}-worker", config.bot.name))
+        .spawn(move || worker.run(receiver))?;
+
+    Ok(sender)
+}
+
+impl Worker {
+    fn run(mut self, events: Receiver<Event>) {
+        while let Ok(event) = events.recv() {
+            let batch: Vec<Event> = std::iter::once(event).chain(events.try_iter()).collect();
+            let last_patch = batch
+                .iter()
+                .rposition(|event| matches!(event, Event::Patch { .. }));
+
+            for (index, event) in batch.into_iter().enumerate() {
+                match event {
+                    Event::GameStart(game_start) => self.game_start(game_start),
+                    Event::Patch {
+                        patch,
+                        received,
+                        socket,
+                    } => {
+                        if Some(index) == last_patch {
+                            self.answer(&patch, received, &socket);
+                        } else {
+                            self.skip(&patch);
+                        }
+                    }
+                    Event::Teams(teams) => {
+                        self.teams = teams;
+                        record(&mut self.recorder, || Record::Teams {
+                            teams: self.teams.clone(),
+                        });
+                    }
+                    Event::Win { winner } => self.win(winner),
+                    Event::Reload(config) => {
+                        self.config = config;
+                        self.bot.set_config(config);
+                    }
+                }
+            }
+        }
+    }
+
+    fn game_start(&mut self, game_start: GameStart) {
+        let game = self.games;
+        self.games += 1;
+
+        if let Some(dir) = self.config.replay_dir {
+            self.recorder = Recorder::create(dir, self.config, game)
+                .map_err(|err| error!("Failed to start recording: {:?}", err))
+                .ok();
+        }
+
+        record(&mut self.recorder, || Record::GameStart(game_start.clone()));
+        record(&mut self.recorder, || Record::Teams {
+            teams: self.teams.clone(),
+        });
+
+        self.bot.start(game_start);
+        self.bot.reseed(game);
+    }
+
+    fn skip(&mut self, patch: &Patch) {
+        record(&mut self.recorder, || Record::Stale(patch.clone()));
+
+        self.bot.patch(patch);
+        self.bot.update_teammates(&patch.rank, &self.teams);
+        self.stale += 1;
+    }
+
+    fn answer(&mut self, patch: &Patch, received: Instant, socket: &RawClient) {
+        record(&mut self.recorder, || Record::Patch(patch.clone()));
+
+        self.bot.patch(patch);
+
+        let deadline = received + self.bot.think_time();
+        let movement = self.bot.next_move_by(deadline);
+
+        if let Some(movement) = movement {
+            if let Err(err) = socket.emit("move", json!(movement)) {
+                error!("{} failed to move: {:?}", self.config.bot.name, err);
+            }
+        }
+        record(&mut self.recorder, || Record::Move {
+            movement,
+            timed_out: self.bot.timed_out(),
+        });
+
+        self.latency
+            .record(received.elapsed(), self.bot.clock().turn_duration());
+
+        if self.config.bot.render {
+            print!("{}{}", render::CLEAR, render::render(&self.bot));
+        }
+
+        self.bot.update_teammates(&patch.rank, &self.teams);
+    }
+
+    fn win(&mut self, winner: String) {
+        let name = self.config.bot.name;
+
+        record(&mut self.recorder, || Record::Win { winner });
+        self.recorder = None;
+
+        if self.latency.count() > 0 {
+            info!("{} decision latency: {}", name, self.latency);
+        }
+        if self.stale > 0 {
+            info!("{} skipped {} stale patches", name, self.stale);
+        }
+        self.latency.clear();
+        self.stale = 0;
+
+        self.bot.reset();
+        self.bot.teammates.clear();
+    }
+}
@@ -0,0 +1,205 @@
+use polygen_bot::{
+    bot::{Bot, Movement},
+    event::{GameStart, Patch},
+    map::{Land, LandType, Map, MaybeLand},
+    sim::leak_bot_data,
+    strategy::gather,
+    BotData,
+};
+
+fn bot_data() -> &'static BotData {
+    leak_bot_data(
+        r#"
+        cookie = ""
+        room = "sim"
+        auto_ready = false
+        name = "Bot"
+        gather_interval = 10
+        threat_radius = 0
+        "#,
+    )
+}
+
+fn land(color: u8, r#type: LandType, amount: i32) -> Land {
+    Land {
+        color,
+        r#type,
+        amount,
+    }
+}
+
+/// A square map of `width` x `height` tiles of our land holding `amount`
+/// armies each.
+fn owned(width: usize, height: usize, amount: i32) -> Map {
+    let mut gm = Map::with_size(width, height, "四边形".to_string());
+    gm.iter_mut()
+        .for_each(|(_, tile)| *tile = land(1, LandType::Land, amount));
+    gm
+}
+
+#[test]
+fn moves_run_from_the_leaves_to_the_root() {
+    let gm = owned(5, 5, 3);
+    let root = (3, 3);
+    let moves = gather::plan(&gm, 1, root, usize::MAX);
+
+    assert_eq!(moves.len(), 24);
+    assert_eq!(moves.last().map(|&(_, to, _)| to), Some(root));
+
+    for (index, &(from, to, _)) in moves.iter().enumerate() {
+        assert!(gm.adjacent(from).contains(&to));
+
+        // Nothing arrives at a tile after its armies have left.
+        assert!(moves[index + 1..]
+            .iter()
+            .all(|&(later, to, _)| later != from && to != from));
+    }
+}
+
+#[test]
+fn plan_stays_within_its_budget() {
+    let gm = owned(10, 10, 5);
+
+    for budget in [2, 7, 30] {
+        let moves = gather::plan(&gm, 1, (1, 1), budget);
+        assert!(!moves.is_empty());
+        assert!(
+            moves.len() <= budget,
+            "{} moves for {}",
+            moves.len(),
+            budget
+        );
+    }
+}
+
+#[test]
+fn plan_never_passes_through_the_general() {
+    let mut gm = owned(5, 1, 5);
+    gm[(1, 3)] = land(1, LandType::General, 50);
+
+    let moves = gather::plan(&gm, 1, (1, 1), usize::MAX);
+
+    assert_eq!(moves, [((1, 2), (1, 1), false)]);
+}
+
+#[test]
+fn single_armies_are_left_behind() {
+    let mut gm = owned(3, 1, 2);
+    assert!(gather::plan(&gm, 1, (1, 1), usize::MAX).is_empty());
+
+    gm[(1, 3)].amount = 5;
+    assert_eq!(
+        gather::plan(&gm, 1, (1, 1), usize::MAX),
+        [((1, 3), (1, 2), false), ((1, 2), (1, 1), false)]
+    );
+}
+
+/// Plays `movement` on `gm`, returning what changed as the server would.
+fn play(gm: &mut Map, (from, to, _): Movement) -> Patch {
+    let old = [gm[from], gm[to]];
+    let moved = gm[from].amount - 1;
+    gm[from].amount = 1;
+
+    if gm[to].color == gm[from].color {
+        gm[to].amount += moved;
+    } else if moved > gm[to].amount {
+        gm[to].color = gm[from].color;
+        gm[to].amount = moved - gm[to].amount;
+    } else {
+        gm[to].amount -= moved;
+    }
+
+    let updates = [from, to]
+        .into_iter()
+        .zip(old)
+        .map(|(pos, old)| {
+            let new = gm[pos];
+            let data = MaybeLand {
+                c: (new.color != old.color).then_some(new.color),
+                t: None,
+                a: Some(new.amount - old.amount),
+            };
+            (gm.id(pos), data)
+        })
+        .collect();
+
+    Patch {
+        updates,
+        rank: Vec::new(),
+    }
+}
+
+/// Our general, four tiles of 5 and strong neutral land in front of the
+/// enemy general in the top row, and mountains below but for a neutral
+/// tile under our general.
+fn front() -> Map {
+    let mut gm = owned(7, 2, 5);
+    gm[(1, 1)] = land(1, LandType::General, 2);
+    gm[(1, 6)] = land(0, LandType::Land, 30);
+    gm[(1, 7)] = land(2, LandType::General, 10);
+
+    for j in 1..=7 {
+        gm[(2, j)] = land(0, LandType::Mountain, 0);
+    }
+    gm[(2, 1)] = land(0, LandType::Land, 5);
+
+    gm
+}
+
+/// Plays `turns` moves of a bot with a gather due on the first of them.
+fn gather_on(mut gm: Map, turns: usize) -> (Bot, Vec<Movement>) {
+    let mut bot = Bot::new(bot_data()).unwrap();
+    bot.start(GameStart {
+        maybe_map: gm.maybe_map(),
+        my_color: 1,
+    });
+
+    let idle = Patch {
+        updates: Vec::new(),
+        rank: Vec::new(),
+    };
+
+    // Gathers are planned on multiples of `gather_interval`.
+    for _ in 0..9 {
+        bot.patch(&idle);
+    }
+
+    let mut moves = Vec::new();
+    let mut patch = idle;
+
+    for _ in 0..turns {
+        bot.patch(&patch);
+        let movement = bot.next_move().unwrap();
+        moves.push(movement);
+        patch = play(&mut gm, movement);
+    }
+
+    (bot, moves)
+}
+
+#[test]
+fn gathered_stack_heads_for_the_general() {
+    let (bot, moves) = gather_on(front(), 4);
+
+    assert_eq!(
+        moves,
+        [
+            ((1, 2), (1, 3), false),
+            ((1, 3), (1, 4), false),
+            ((1, 4), (1, 5), false),
+            ((1, 5), (1, 6), false),
+        ]
+    );
+    assert_eq!(bot.from(), Some((1, 6)));
+    assert_eq!(bot.target(), Some((1, 7)));
+}
+
+#[test]
+fn tiles_outside_the_tree_keep_expanding() {
+    let mut gm = front();
+    gm[(1, 1)].amount = 10;
+
+    let (_, moves) = gather_on(gm, 2);
+
+    assert_eq!(moves, [((1, 1), (2, 1), false), ((1, 2), (1, 3), false)]);
+}
@@ -0,0 +1,85 @@
+use polygen_bot::{
+    map::LandType,
+    sim::{leak_bot_data, Game, Options},
+    BotData,
+};
+use std::thread;
+
+fn bot_data(name: &str) -> &'static BotData {
+    leak_bot_data(&format!(
+        r#"
+        cookie = ""
+        room = "sim"
+        auto_ready = false
+        name = "{}"
+        "#,
+        name
+    ))
+}
+
+/// Compares the first bot's guess of where the second bot's general is,
+/// while it is hidden, with a random pick among the tiles still thought
+/// possible: returns the mean distance of each from the real general.
+fn guess_and_random(seed: u64) -> (f64, f64) {
+    let options = Options {
+        width: 30,
+        height: 30,
+        max_turns: 300,
+        ..Default::default()
+    };
+    let mut game = Game::new(&[bot_data("a"), bot_data("b")], &options, seed).unwrap();
+    let (mut guess, mut random) = (0.0, 0.0);
+
+    while game.step().is_none() {
+        let gm = game.map();
+        let Some((general, _)) = gm
+            .iter()
+            .into_iter()
+            .find(|(_, land)| land.r#type == LandType::General && land.color == 2)
+        else {
+            break;
+        };
+
+        let Some(belief) = game.bot(0).inference.belief(2) else {
+            continue;
+        };
+        let Some((pos, prob)) = belief.most_likely() else {
+            continue;
+        };
+
+        if !belief.informed || prob >= 1.0 {
+            continue;
+        }
+
+        let dist = gm.distances([general], u32::MAX);
+        let candidates: Vec<f64> = gm
+            .iter()
+            .into_iter()
+            .filter(|&(pos, _)| belief.probability(pos) > 0.0)
+            .filter_map(|(pos, _)| dist[gm.id(pos)])
+            .map(f64::from)
+            .collect();
+
+        guess += f64::from(dist[gm.id(pos)].unwrap());
+        random += candidates.iter().sum::<f64>() / candidates.len() as f64;
+    }
+
+    (guess, random)
+}
+
+#[test]
+fn most_likely_general_is_near_the_real_one() {
+    let results: Vec<(f64, f64)> = thread::scope(|scope| {
+        let games: Vec<_> = (0..12)
+            .map(|seed| scope.spawn(move || guess_and_random(seed)))
+            .collect();
+
+        games.into_iter().map(|game| game.join().unwrap()).collect()
+    });
+
+    let guess: f64 = results.iter().map(|(guess, _)| guess).sum();
+    let random: f64 = results.iter().map(|(_, random)| random).sum();
+
+    assert!(guess > 0.0);
+    assert!(guess < random * 0.7, "{} vs {}", guess, random);
+}
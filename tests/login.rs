@@ -0,0 +1,156 @@
+use polygen_bot::{
+    cookie::Cookie,
+    login::{self, Credentials},
+    mock::MockServer,
+    socket::new_bot,
+    BotData,
+};
+use serde_json::json;
+use std::{env, fs, path::PathBuf, time::Duration};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A fresh path in the temporary directory for `test`.
+fn temp_path(test: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("polygen_bot-{}-{}", test, std::process::id()));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+fn credentials<'a>(password: &'a str, store: Option<&'a str>) -> Credentials<'a> {
+    Credentials {
+        username: "Bot",
+        password,
+        path: "/api/login",
+        store,
+    }
+}
+
+#[test]
+fn login_returns_the_session_cookie() {
+    let server = MockServer::start().unwrap();
+    server.require_login("Bot", "secret");
+
+    let cookie = login::login(&server.url(), &credentials("secret", None)).unwrap();
+    assert!(cookie.starts_with("__session=mock-"));
+
+    assert!(login::login(&server.url(), &credentials("wrong", None)).is_err());
+}
+
+#[test]
+fn login_reuses_the_stored_session() {
+    let server = MockServer::start().unwrap();
+    server.require_login("Bot", "secret");
+
+    let store = temp_path("stored");
+    let store = store.to_str().unwrap();
+    let credentials = credentials("secret", Some(store));
+
+    let first = login::session(&server.url(), &credentials).unwrap();
+    assert_eq!(fs::read_to_string(store).unwrap(), first);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(store).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    assert_eq!(login::session(&server.url(), &credentials).unwrap(), first);
+
+    let refreshed = login::refresh(&server.url(), &credentials).unwrap();
+    assert_ne!(refreshed, first);
+    assert_eq!(fs::read_to_string(store).unwrap(), refreshed);
+
+    fs::remove_file(store).unwrap();
+}
+
+#[test]
+fn bot_logs_in_again_when_its_session_is_rejected() {
+    let server = MockServer::start().unwrap();
+    server.require_login("Bot", "secret");
+
+    // Logging in once creates the store with the right permissions; then
+    // the session in it expires.
+    let store = temp_path("rejected");
+    login::refresh(
+        &server.url(),
+        &credentials("secret", Some(store.to_str().unwrap())),
+    )
+    .unwrap();
+    fs::write(&store, "__session=expired").unwrap();
+
+    let store: &'static str = store.to_str().unwrap().to_string().leak();
+    let bot = r#"
+        room = "161"
+        auto_ready = true
+        name = "Bot"
+        cookie = "unused"
+    "#;
+    let config: &'static BotData = Box::leak(Box::new(BotData {
+        bot: polygen_bot::BotConfig {
+            cookie: Cookie::Login(credentials("secret", Some(store))),
+            ..toml::from_str(bot).unwrap()
+        },
+        room: None,
+        base_url: server.url().leak(),
+        replay_dir: None,
+    }));
+
+    let client = new_bot(config).unwrap();
+
+    assert_eq!(server.wait_for("joinRoom", TIMEOUT), Some(json!("161")));
+
+    let cookies = server.cookies();
+    assert_eq!(
+        cookies.first().map(String::as_str),
+        Some("__session=expired")
+    );
+    assert_eq!(cookies.last(), Some(&fs::read_to_string(store).unwrap()));
+
+    client.disconnect().unwrap();
+    fs::remove_file(store).unwrap();
+}
+
+#[test]
+fn bot_logs_in_again_when_its_session_expires_while_connected() {
+    let server = MockServer::start().unwrap();
+    server.require_login("Bot", "secret");
+
+    let store = temp_path("expired");
+    let store: &'static str = store.to_str().unwrap().to_string().leak();
+    let bot = r#"
+        room = "161"
+        auto_ready = true
+        name = "Bot"
+        cookie = "unused"
+        reconnect = { attempts = 3, min_delay_ms = 10, max_delay_ms = 100 }
+    "#;
+    let config: &'static BotData = Box::leak(Box::new(BotData {
+        bot: polygen_bot::BotConfig {
+            cookie: Cookie::Login(credentials("secret", Some(store))),
+            ..toml::from_str(bot).unwrap()
+        },
+        room: None,
+        base_url: server.url().leak(),
+        replay_dir: None,
+    }));
+
+    let client = new_bot(config).unwrap();
+    assert!(server.wait_for("joinRoom", TIMEOUT).is_some());
+    let first = fs::read_to_string(store).unwrap();
+
+    server.expire_sessions();
+    server.disconnect_all();
+
+    assert_eq!(server.wait_for("joinRoom", TIMEOUT), Some(json!("161")));
+
+    let cookies = server.cookies();
+    let second = fs::read_to_string(store).unwrap();
+    assert_ne!(second, first);
+    assert!(cookies.contains(&first));
+    assert_eq!(cookies.last(), Some(&second));
+
+    client.disconnect().unwrap();
+    fs::remove_file(store).unwrap();
+}
@@ -0,0 +1,14 @@
+use polygen_bot::map::Map;
+
+#[test]
+fn neighbours_past_the_127th_row_and_column() {
+    let gm = Map::with_size(200, 200, "四边形".to_string());
+    let mut neighbours = gm.neighbours((150, 130)).to_vec();
+    neighbours.sort_unstable();
+
+    assert_eq!(neighbours, [(149, 130), (150, 129), (150, 131), (151, 130)]);
+
+    let gm = Map::with_size(200, 200, "六边形".to_string());
+    assert_eq!(gm.neighbours((150, 130)).len(), 6);
+    assert_eq!(gm.neighbours((200, 200)).len(), 2);
+}
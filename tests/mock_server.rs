@@ -0,0 +1,224 @@
+use polygen_bot::{
+    event::{GameStart, Patch},
+    map::{MaybeLand, MaybeMap},
+    mock::MockServer,
+    socket::{new_bot, BotHandle, Status},
+    BotData, RoomConfig,
+};
+use serde_json::json;
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+fn bot_data(base_url: String) -> &'static BotData {
+    let bot = r#"
+        cookie = "__session=test"
+        room = "161"
+        auto_ready = true
+        name = "Bot"
+        reconnect = { attempts = 3, min_delay_ms = 10, max_delay_ms = 100 }
+    "#;
+
+    Box::leak(Box::new(BotData {
+        bot: toml::from_str(bot).unwrap(),
+        room: None,
+        base_url: Box::leak(base_url.into_boxed_str()),
+        replay_dir: None,
+    }))
+}
+
+/// Waits for `client` to reach `status`, returning whether it did.
+fn wait_for_status(client: &BotHandle, status: Status) -> bool {
+    let deadline = Instant::now() + TIMEOUT;
+
+    while client.status() != status {
+        if Instant::now() >= deadline {
+            return false;
+        }
+
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    true
+}
+
+fn land(c: u8, t: u8, a: i32) -> MaybeLand {
+    MaybeLand {
+        c: Some(c),
+        t: Some(t),
+        a: Some(a),
+    }
+}
+
+/// A 2x2 square map with our general in the top left corner.
+fn game_start() -> GameStart {
+    let empty = MaybeLand {
+        c: None,
+        t: None,
+        a: None,
+    };
+
+    GameStart {
+        maybe_map: MaybeMap {
+            width: 2,
+            height: 2,
+            gm: vec![
+                vec![empty; 3],
+                vec![empty, land(1, 1, 5), land(0, 0, 0)],
+                vec![empty, land(0, 0, 0), land(0, 5, 0)],
+            ],
+            mode: "四边形".to_string(),
+        },
+        my_color: 1,
+    }
+}
+
+#[test]
+fn bot_joins_votes_and_moves() {
+    let server = MockServer::start().unwrap();
+    let client = new_bot(bot_data(server.url())).unwrap();
+
+    assert_eq!(server.wait_for("joinRoom", TIMEOUT), Some(json!("161")));
+    assert!(server.wait_for("ready", TIMEOUT).is_some());
+    assert_eq!(server.cookies(), ["__session=test"]);
+
+    server.emit_game_start(&game_start()).unwrap();
+    server
+        .emit_patch(&Patch {
+            updates: vec![(1, land(1, 1, 1))],
+            rank: vec![(None, 1, "Bot".to_string(), 1, 6)],
+        })
+        .unwrap();
+
+    let movement = server.wait_for("move", TIMEOUT).unwrap();
+    assert_eq!(movement[0], json!([1, 1]));
+
+    server.emit_win("Bot");
+    assert!(server.wait_for("ready", TIMEOUT).is_some());
+
+    client.disconnect().unwrap();
+}
+
+#[test]
+fn bot_rejoins_after_being_kicked() {
+    let server = MockServer::start().unwrap();
+    server.on("joinRoom", |_| {
+        vec![("updateTeams".to_string(), json!([[1, ["Bot"]]]))]
+    });
+
+    let client = new_bot(bot_data(server.url())).unwrap();
+    assert!(server.wait_for("joinRoom", TIMEOUT).is_some());
+
+    server.disconnect_all();
+
+    assert_eq!(server.wait_for("joinRoom", TIMEOUT), Some(json!("161")));
+    assert!(server.wait_for("ready", TIMEOUT).is_some());
+
+    client.disconnect().unwrap();
+}
+
+#[test]
+fn bot_reconnects_after_losing_its_connection() {
+    let server = MockServer::start().unwrap();
+    let client = new_bot(bot_data(server.url())).unwrap();
+    assert!(server.wait_for("joinRoom", TIMEOUT).is_some());
+
+    server.hang_up_all();
+
+    assert_eq!(server.wait_for("joinRoom", TIMEOUT), Some(json!("161")));
+    assert!(wait_for_status(&client, Status::Connected));
+
+    client.disconnect().unwrap();
+    assert_eq!(client.status(), Status::Stopped);
+}
+
+#[test]
+fn bot_fails_once_every_attempt_is_rejected() {
+    let server = MockServer::start().unwrap();
+    let client = new_bot(bot_data(server.url())).unwrap();
+    assert!(server.wait_for("joinRoom", TIMEOUT).is_some());
+
+    // Nobody can log in, so every handshake from now on is rejected.
+    server.require_login("nobody", "");
+    server.disconnect_all();
+
+    assert!(wait_for_status(&client, Status::Failed));
+
+    // Failing means the attempts are over.
+    let handshakes = server.cookies().len();
+    thread::sleep(Duration::from_millis(300));
+    assert!(handshakes > 1);
+    assert_eq!(server.cookies().len(), handshakes);
+}
+
+#[test]
+fn bot_reconnects_after_losing_connections_before_they_open() {
+    let server = MockServer::start().unwrap();
+    let client = new_bot(bot_data(server.url())).unwrap();
+    assert!(server.wait_for("joinRoom", TIMEOUT).is_some());
+
+    // Two of the three attempts are lost during the handshake.
+    server.hang_up_handshakes(2);
+    server.hang_up_all();
+
+    assert_eq!(server.wait_for("joinRoom", TIMEOUT), Some(json!("161")));
+    assert!(wait_for_status(&client, Status::Connected));
+
+    client.disconnect().unwrap();
+}
+
+#[test]
+fn bot_fails_once_every_connection_is_lost_before_it_opens() {
+    let server = MockServer::start().unwrap();
+    let client = new_bot(bot_data(server.url())).unwrap();
+    assert!(server.wait_for("joinRoom", TIMEOUT).is_some());
+
+    server.hang_up_handshakes(usize::MAX);
+    server.hang_up_all();
+
+    assert!(wait_for_status(&client, Status::Failed));
+}
+
+#[test]
+fn bot_reloads_config_after_the_game() {
+    let server = MockServer::start().unwrap();
+    let config = bot_data(server.url());
+    let client = new_bot(config).unwrap();
+
+    assert!(server.wait_for("ready", TIMEOUT).is_some());
+
+    server.emit_game_start(&game_start()).unwrap();
+    server
+        .emit_patch(&Patch {
+            updates: vec![(1, land(1, 1, 1))],
+            rank: vec![(None, 1, "Bot".to_string(), 1, 6)],
+        })
+        .unwrap();
+    assert!(server.wait_for("move", TIMEOUT).is_some());
+
+    let reloaded = Box::leak(Box::new(BotData {
+        room: Some(RoomConfig {
+            mode: None,
+            map: None,
+            speed: Some(2.0),
+        }),
+        ..config.clone()
+    }));
+    client.reload(reloaded).unwrap();
+
+    assert!(!server.received().iter().any(|(event, _)| event == "vote"));
+    assert_eq!(client.config().room, None);
+
+    server.emit_win("Bot");
+
+    assert_eq!(
+        server.wait_for("vote", TIMEOUT),
+        Some(json!({"item": "speed", "value": 2.0}))
+    );
+    assert_eq!(client.config(), &*reloaded);
+
+    client.disconnect().unwrap();
+}
@@ -0,0 +1,208 @@
+use polygen_bot::{
+    bot::Bot,
+    event::{GameStart, Patch, Updates},
+    map::{Map, MaybeLand, MaybeMap},
+    replay::{Record, Recorder, Replay},
+    sim::{leak_bot_data, Game, Options},
+    BotData,
+};
+use std::{
+    fs,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+fn bot_data(name: &str) -> &'static BotData {
+    bot_data_with(name, 1)
+}
+
+fn bot_data_with(name: &str, calc_cnt: u8) -> &'static BotData {
+    leak_bot_data(&format!(
+        r#"
+        cookie = ""
+        room = "sim"
+        auto_ready = false
+        name = "{}"
+        seed = 7
+        calc_cnt = {}
+        "#,
+        name, calc_cnt
+    ))
+}
+
+/// What changed between two views, as the server would send it.
+fn diff(old: &Map, new: &Map) -> Updates {
+    let mut updates = Updates::new();
+
+    for (pos, land) in new.iter() {
+        let old = &old[pos];
+
+        if old.color != land.color || old.r#type != land.r#type || old.amount != land.amount {
+            updates.push((
+                new.id(pos),
+                MaybeLand {
+                    c: (old.color != land.color).then_some(land.color),
+                    t: (old.r#type != land.r#type).then_some(land.r#type as u8),
+                    a: (old.amount != land.amount).then_some(land.amount - old.amount),
+                },
+            ));
+        }
+    }
+
+    updates
+}
+
+#[test]
+fn replays_repeat_moves_chosen_on_a_deadline() {
+    let dir = std::env::temp_dir().join(format!("polygen_bot-replay-{}", std::process::id()));
+    let config = bot_data("live");
+    let options = Options {
+        max_turns: 200,
+        ..Default::default()
+    };
+
+    // A third bot watches color 1 of a simulated game and records its own
+    // moves, chosen on a deadline short enough to cut searches short.
+    let mut game = Game::new(&[bot_data("a"), bot_data("b")], &options, 0).unwrap();
+    let mut live = Bot::new(config).unwrap();
+    let mut recorder = Recorder::create(&dir, config, 0).unwrap();
+    live.reseed(0);
+
+    let mut view = Game::view(game.map(), 1);
+    let game_start = GameStart {
+        maybe_map: view.maybe_map(),
+        my_color: 1,
+    };
+    live.start(game_start.clone());
+    recorder.record(Record::GameStart(game_start)).unwrap();
+
+    loop {
+        let outcome = game.step();
+        let new_view = Game::view(game.map(), 1);
+        let patch = Patch {
+            updates: diff(&view, &new_view),
+            rank: game.rank(),
+        };
+        view = new_view;
+
+        live.patch(&patch);
+        let movement = live.next_move_by(Instant::now() + Duration::from_micros(200));
+        recorder.record(Record::Patch(patch)).unwrap();
+        recorder
+            .record(Record::Move {
+                movement,
+                timed_out: live.timed_out(),
+            })
+            .unwrap();
+
+        if outcome.is_some() {
+            break;
+        }
+    }
+
+    assert_repeats(config, recorder, &dir);
+}
+
+fn land(c: u8, t: u8, a: i32) -> MaybeLand {
+    MaybeLand {
+        c: Some(c),
+        t: Some(t),
+        a: Some(a),
+    }
+}
+
+/// A 5x3 square map with our general in the top left corner, neutral
+/// cities too strong to take, and three neutral lands to head for, two of
+/// them walled off by mountains.
+fn walled_targets() -> GameStart {
+    let empty = MaybeLand {
+        c: None,
+        t: None,
+        a: None,
+    };
+    let city = land(0, 2, 40);
+    let mountain = land(0, 3, 0);
+    let target = land(0, 0, 0);
+
+    GameStart {
+        maybe_map: MaybeMap {
+            width: 5,
+            height: 3,
+            gm: vec![
+                vec![empty; 6],
+                vec![empty, land(1, 1, 10), city, city, mountain, target],
+                vec![empty, mountain, city, target, mountain, mountain],
+                vec![empty, target, mountain, city, city, city],
+            ],
+            mode: "四边形".to_string(),
+        },
+        my_color: 1,
+    }
+}
+
+#[test]
+fn replays_repeat_moves_after_unreachable_targets() {
+    // Searching again after a walled off target draws new targets, so
+    // whether the deadline had passed by then must be replayed too.
+    for (index, think) in [Duration::ZERO, Duration::from_secs(1)]
+        .into_iter()
+        .enumerate()
+    {
+        let dir = std::env::temp_dir().join(format!(
+            "polygen_bot-replay-walled-{}-{}",
+            std::process::id(),
+            index
+        ));
+        let config = bot_data_with("live", 3);
+        let mut live = Bot::new(config).unwrap();
+        let mut recorder = Recorder::create(&dir, config, 0).unwrap();
+        live.reseed(0);
+
+        let game_start = walled_targets();
+        live.start(game_start.clone());
+        recorder.record(Record::GameStart(game_start)).unwrap();
+
+        for _ in 0..10 {
+            // Only the general grows; nothing the bot does changes the map.
+            let patch = Patch {
+                updates: vec![(1, land(1, 1, 1))],
+                rank: vec![(None, 1, "live".to_string(), 10, 1)],
+            };
+
+            live.patch(&patch);
+            let movement = live.next_move_by(Instant::now() + think);
+            recorder.record(Record::Patch(patch)).unwrap();
+            recorder
+                .record(Record::Move {
+                    movement,
+                    timed_out: live.timed_out(),
+                })
+                .unwrap();
+        }
+
+        assert_repeats(config, recorder, &dir);
+    }
+}
+
+/// Reruns what `recorder` recorded and checks that every move is repeated.
+fn assert_repeats(config: &'static BotData, recorder: Recorder, dir: &Path) {
+    let path = recorder.path().to_path_buf();
+    drop(recorder);
+
+    let replay = Replay::open(&path).unwrap();
+    let mut bot = Bot::new(config).unwrap();
+    let mut turns = 0;
+    let mut diverged = Vec::new();
+
+    replay.rerun(&mut bot, |_, turn| {
+        turns += 1;
+        if turn.diverged() {
+            diverged.push(turn.index);
+        }
+    });
+
+    fs::remove_dir_all(dir).unwrap();
+
+    assert!(turns > 0);
+    assert!(diverged.is_empty(), "diverged at turns {:?}", diverged);
+}
@@ -0,0 +1,48 @@
+use polygen_bot::{
+    bot::Movement,
+    sim::{leak_bot_data, Game, Options, Outcome},
+    BotData,
+};
+
+fn bot_data(name: &str) -> &'static BotData {
+    leak_bot_data(&format!(
+        r#"
+        cookie = ""
+        room = "sim"
+        auto_ready = false
+        name = "{}"
+        "#,
+        name
+    ))
+}
+
+/// Plays a whole game, returning its outcome and every move of both bots.
+fn play(seed: u64) -> (Outcome, Vec<[Option<Movement>; 2]>) {
+    let options = Options {
+        max_turns: 300,
+        ..Default::default()
+    };
+    let mut game = Game::new(&[bot_data("a"), bot_data("b")], &options, seed).unwrap();
+    let mut moves = Vec::new();
+
+    loop {
+        let outcome = game.step();
+        moves.push([game.bot(0).last_move(), game.bot(1).last_move()]);
+
+        if let Some(outcome) = outcome {
+            return (outcome, moves);
+        }
+    }
+}
+
+#[test]
+fn same_seed_replays_the_same_game() {
+    for seed in [0, 1] {
+        let (first, first_moves) = play(seed);
+        let (second, second_moves) = play(seed);
+
+        assert_eq!(first.winner, second.winner, "seed {}", seed);
+        assert_eq!(first.turns, second.turns, "seed {}", seed);
+        assert_eq!(first_moves, second_moves, "seed {}", seed);
+    }
+}
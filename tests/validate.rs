@@ -0,0 +1,171 @@
+use polygen_bot::{
+    validate::{validate, Problem, Severity},
+    Config,
+};
+
+fn problems(config: &str) -> Vec<Problem> {
+    let config: Config = toml::from_str(config).unwrap();
+    validate(&config)
+}
+
+#[test]
+fn valid_config_has_no_problems() {
+    let problems = problems(
+        r#"
+        base_url = "http://localhost"
+
+        [[bots]]
+        cookie = "__session=test"
+        room = "161"
+        auto_ready = true
+        name = "Bot"
+
+        [rooms.161]
+        speed = 2.0
+        "#,
+    );
+
+    assert!(problems.is_empty(), "{:?}", problems);
+}
+
+#[test]
+fn duplicate_names_in_a_room_are_errors() {
+    let problems = problems(
+        r#"
+        base_url = "http://localhost"
+
+        [[bots]]
+        cookie = "__session=a"
+        room = "161"
+        auto_ready = true
+        name = "Bot"
+
+        [[bots]]
+        cookie = "__session=b"
+        room = "161"
+        auto_ready = true
+        name = "Bot"
+
+        [[bots]]
+        cookie = "__session=c"
+        room = "162"
+        auto_ready = true
+        name = "Bot"
+
+        [rooms.161]
+        [rooms.162]
+        "#,
+    );
+
+    assert_eq!(problems.len(), 1, "{:?}", problems);
+    assert_eq!(problems[0].severity, Severity::Error);
+    assert_eq!(problems[0].location, "bots[1] (`Bot`).name");
+}
+
+#[test]
+fn speed_out_of_range_is_an_error() {
+    let problems = problems(
+        r#"
+        base_url = "http://localhost"
+        bots = []
+
+        [rooms.fast]
+        speed = 8.0
+
+        [rooms.slow]
+        speed = 0.1
+
+        [rooms.ok]
+        speed = 4.0
+        "#,
+    );
+
+    let locations: Vec<_> = problems
+        .iter()
+        .map(|problem| (problem.severity, problem.location.as_str()))
+        .collect();
+
+    assert_eq!(
+        locations,
+        [
+            (Severity::Error, "rooms.`fast`.speed"),
+            (Severity::Error, "rooms.`slow`.speed"),
+        ]
+    );
+}
+
+#[test]
+fn doubtful_settings_only_warn() {
+    let problems = problems(
+        r#"
+        base_url = "http://localhost"
+
+        [[bots]]
+        cookie = "__session=test"
+        room = "161"
+        auto_ready = true
+        name = "Bot"
+
+        [rooms.162]
+        map = "没有的地图"
+        "#,
+    );
+
+    let locations: Vec<_> = problems
+        .iter()
+        .map(|problem| (problem.severity, problem.location.as_str()))
+        .collect();
+
+    assert_eq!(
+        locations,
+        [
+            (Severity::Warning, "bots[0] (`Bot`).room"),
+            (Severity::Warning, "rooms.`162`.map"),
+        ]
+    );
+}
+
+#[test]
+fn zero_calc_cnt_is_an_error() {
+    let problems = problems(
+        r#"
+        base_url = "http://localhost"
+
+        [[bots]]
+        cookie = "__session=test"
+        room = "161"
+        auto_ready = true
+        name = "Bot"
+        calc_cnt = 0
+
+        [rooms.161]
+        "#,
+    );
+
+    assert_eq!(problems.len(), 1, "{:?}", problems);
+    assert_eq!(problems[0].severity, Severity::Error);
+    assert_eq!(problems[0].location, "bots[0] (`Bot`).calc_cnt");
+}
+
+#[test]
+fn openings_longer_than_the_planner_allows_are_errors() {
+    let problems = problems(
+        r#"
+        base_url = "http://localhost"
+
+        [[bots]]
+        cookie = "__session=test"
+        room = "161"
+        auto_ready = true
+        name = "Bot"
+        opening = "waves"
+        opening_turns = 200
+
+        [rooms.161]
+        "#,
+    );
+
+    assert_eq!(problems.len(), 1, "{:?}", problems);
+    assert_eq!(problems[0].severity, Severity::Error);
+    assert_eq!(problems[0].location, "bots[0] (`Bot`).opening_turns");
+}